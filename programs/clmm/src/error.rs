@@ -36,10 +36,70 @@ pub enum CLMMError {
     AmountTooLarge,
     #[msg("Unexpected error no swap happened")]
     ZeroSwapOutput,
-    #[msg("The tick must be lesser than, or equal to the maximum tick(221818)")]
+    #[msg("The tick must be lesser than, or equal to the maximum tick(443636)")]
     TickUpperOverflow,
-    #[msg("The tick must be greater, or equal to the minimum tick(-221818)")]
+    #[msg("The tick must be greater, or equal to the minimum tick(-443636)")]
     TickLowerOverflow,
     #[msg("sqrt_price_x64 out of range")]
     SqrtPriceX64,
+    #[msg("Tick array does not belong to this pool or has the wrong start index")]
+    InvalidTickArray,
+    #[msg("Tick array is outside the range tracked by the pool bitmap")]
+    TickArrayOutOfRange,
+    #[msg("Signer does not hold the position token")]
+    NotPositionOwner,
+    #[msg("Position does not belong to this pool")]
+    InvalidPosition,
+    #[msg("Position does not hold enough liquidity")]
+    InsufficientPositionLiquidity,
+    #[msg("Position still holds liquidity, uncollected fees or unclaimed rewards")]
+    PositionNotEmpty,
+    #[msg("Fee rate is not one of the allowed fee tiers")]
+    InvalidFeeTier,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Price limit must lie between the current price and the price bounds in the swap direction")]
+    InvalidSqrtPriceLimit,
+    #[msg("Deposit needs more tokens than the maximum allowed")]
+    TokenMaxExceeded,
+    #[msg("Withdrawal pays fewer tokens than the minimum required")]
+    TokenMinNotMet,
+    #[msg("Transaction landed after its deadline")]
+    TransactionExpired,
+    #[msg("Token A's mint must sort before token B's mint")]
+    InvalidMintOrder,
+    #[msg("Mint has a Token-2022 extension pools do not support")]
+    UnsupportedMintExtension,
+    #[msg("Native SOL needs a pool holding the native mint")]
+    NativeMintNotInPool,
+    #[msg("Native SOL needs the signer's ephemeral wSOL account")]
+    InvalidWsolAccount,
+    #[msg("Observation cardinality can only grow, up to the buffer capacity")]
+    InvalidObservationCardinality,
+    #[msg("Requested time is before the oldest observation")]
+    ObservationTooOld,
+    #[msg("Too many observations requested at once")]
+    TooManyObservations,
+    #[msg("Reward index is out of range or not the next free reward slot")]
+    InvalidRewardIndex,
+    #[msg("Reward vault does not hold a day of emissions at the new rate")]
+    InsufficientRewardVaultBalance,
+    #[msg("Pool is locked by a flash loan or flash swap in progress")]
+    PoolLocked,
+    #[msg("Flash borrow must be followed by a flash repay for the same pool")]
+    MissingFlashRepay,
+    #[msg("Flash borrow cannot be called through CPI")]
+    FlashBorrowCpi,
+    #[msg("Pool has no outstanding flash loan")]
+    NoActiveFlashLoan,
+    #[msg("Flash swap needs a callback program after its tick arrays")]
+    MissingCallbackProgram,
+    #[msg("Flash swap callback did not pay the input amount owed")]
+    FlashSwapNotPaid,
+    #[msg("Route hops do not chain the input mint to the output mint")]
+    InvalidRoute,
+    #[msg("A route hop could not swap everything the previous hop sent it")]
+    RouteHopNotFilled,
+    #[msg("Token account is required for a side not paid in native SOL")]
+    MissingTokenAccount,
 }
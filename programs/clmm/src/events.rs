@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct PoolCreated{
+    pub pool:Pubkey,
+    pub amm_config:Pubkey,
+    pub minta:Pubkey,
+    pub mintb:Pubkey,
+    pub fee_rate:u32,
+    pub tick_spacing:u16,
+    pub sqrt_price:u128,
+    pub tick:i32,
+}
+
+#[event]
+pub struct TickInitialized{
+    pub pool:Pubkey,
+    pub tick_array:Pubkey,
+    pub start_tick_index:i32,
+}
+
+/// `amount_a` and `amount_b` include any transfer fee the signer paid
+#[event]
+pub struct LiquidityIncreased{
+    pub pool:Pubkey,
+    pub position:Pubkey,
+    pub tick_lower:i32,
+    pub tick_upper:i32,
+    pub liquidity:u128,
+    pub amount_a:u64,
+    pub amount_b:u64,
+}
+
+/// `amount_a` and `amount_b` are what left the vaults, before transfer fees
+#[event]
+pub struct LiquidityDecreased{
+    pub pool:Pubkey,
+    pub position:Pubkey,
+    pub tick_lower:i32,
+    pub tick_upper:i32,
+    pub liquidity:u128,
+    pub amount_a:u64,
+    pub amount_b:u64,
+}
+
+/// `amount_in` and `amount_out` are what entered and left the pool's vaults, so
+/// transfer fees the signer paid on top or lost on the way out are not in them.
+/// `fee_amount` is the whole swap fee in the input token, `protocol_fee` included.
+#[event]
+pub struct Swapped{
+    pub pool:Pubkey,
+    pub sender:Pubkey,
+    pub a_to_b:bool,
+    pub amount_in:u64,
+    pub amount_out:u64,
+    pub fee_amount:u64,
+    pub protocol_fee:u64,
+    pub sqrt_price_before:u128,
+    pub sqrt_price_after:u128,
+    pub tick_before:i32,
+    pub tick_after:i32,
+    pub liquidity_after:u128,
+}
+
+#[event]
+pub struct FeesCollected{
+    pub pool:Pubkey,
+    pub position:Pubkey,
+    pub amount_a:u64,
+    pub amount_b:u64,
+}
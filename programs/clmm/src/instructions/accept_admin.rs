@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, state::AmmConfig};
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info>{
+    #[account(address=amm_config.pending_admin @ CLMMError::Unauthorized)]
+    pub signer:Signer<'info>,
+    #[account(mut,seeds=[b"amm_config"],bump=amm_config.bump)]
+    pub amm_config:Account<'info,AmmConfig>,
+}
+impl <'info> AcceptAdmin<'info>{
+    pub fn accept_admin(ctx:Context<AcceptAdmin>)->Result<()>{
+        let amm_config=&mut ctx.accounts.amm_config;
+        amm_config.admin=amm_config.pending_admin;
+        amm_config.pending_admin=Pubkey::default();
+        Ok(())
+    }
+}
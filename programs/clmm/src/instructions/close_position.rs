@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{burn, close_account, Burn, CloseAccount, Mint, TokenAccount, TokenInterface};
+
+use crate::{error::CLMMError, mint_extensions::mint_close_authority, state::{pool_signer_seeds, Pool, Position}};
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info>{
+    #[account(mut)]
+    pub signer:Signer<'info>,
+    #[account(mut,address=position.position_mint)]
+    pub position_mint:InterfaceAccount<'info, Mint>,
+    #[account(mut,token::mint=position_mint,token::authority=signer,token::token_program=token_program)]
+    pub position_token_account:InterfaceAccount<'info,TokenAccount>,
+    #[account(seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,seeds=[b"position",position.position_mint.as_ref()],bump=position.bump,constraint=position.pool==config.key() @ CLMMError::InvalidPosition,close=signer)]
+    pub position:Account<'info,Position>,
+    pub token_program:Interface<'info, TokenInterface>,
+}
+impl <'info> ClosePosition<'info>{
+    pub fn close_position(ctx:Context<ClosePosition>)->Result<()>{
+        let position=&ctx.accounts.position;
+        require!(position.liquidity==0 && position.tokens_owed_a==0 && position.tokens_owed_b==0,CLMMError::PositionNotEmpty);
+        // rewards are settled into amount_owed whenever liquidity changes, so none can still be accruing
+        require!(position.reward_infos.iter().all(|reward|reward.amount_owed==0),CLMMError::PositionNotEmpty);
+        let account=Burn{
+            mint:ctx.accounts.position_mint.to_account_info(),
+            from:ctx.accounts.position_token_account.to_account_info(),
+            authority:ctx.accounts.signer.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new(ctx.accounts.token_program.to_account_info(), account);
+        burn(cpi_ctx, 1)?;
+        let account=CloseAccount{
+            account:ctx.accounts.position_token_account.to_account_info(),
+            destination:ctx.accounts.signer.to_account_info(),
+            authority:ctx.accounts.signer.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new(ctx.accounts.token_program.to_account_info(), account);
+        close_account(cpi_ctx)?;
+        // with the token burned the mint is empty, and the pool can close it if it may
+        let pool=&ctx.accounts.config;
+        if mint_close_authority(&ctx.accounts.position_mint)?==Some(pool.key()){
+            let signer_seed=pool_signer_seeds!(pool);
+            let account=CloseAccount{
+                account:ctx.accounts.position_mint.to_account_info(),
+                destination:ctx.accounts.signer.to_account_info(),
+                authority:pool.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), account, signer_seed);
+            close_account(cpi_ctx)?;
+        }
+        Ok(())
+    }
+}
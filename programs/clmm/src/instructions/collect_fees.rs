@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{error::CLMMError, events::FeesCollected, state::{pool_signer_seeds, Pool, Position, TickArray, TickState}, utils::tick_array_start_index};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CollectFees<'info>{
+    pub signer:Signer<'info>,
+    #[account(mint::token_program=token_program_a)]
+    pub minta:InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program=token_program_b)]
+    pub mintb:InterfaceAccount<'info, Mint>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=signer)]
+    pub usertoken_account_a:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=signer)]
+    pub usertoken_account_b:InterfaceAccount<'info,TokenAccount>,
+    #[account(constraint=position_token_account.mint==position.position_mint && position_token_account.owner==signer.key() && position_token_account.amount==1 @ CLMMError::NotPositionOwner)]
+    pub position_token_account:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,seeds=[b"position",position.position_mint.as_ref()],bump=position.bump,constraint=position.pool==config.key() @ CLMMError::InvalidPosition)]
+    pub position:Account<'info,Position>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config)]
+    pub vaulta:InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config)]
+    pub  vault_b:InterfaceAccount<'info, TokenAccount>,
+    /// may be the same account as `tick_array_upper` when both ticks share an array
+    pub tick_array_lower:AccountLoader<'info,TickArray>,
+    pub tick_array_upper:AccountLoader<'info,TickArray>,
+    pub token_program_a:Interface<'info, TokenInterface>,
+    pub token_program_b:Interface<'info, TokenInterface>,
+}
+impl <'info> CollectFees<'info>{
+    pub fn collect_fees(ctx:Context<CollectFees>)->Result<()>{
+        let pool=&ctx.accounts.config;
+        require!(pool.minta==ctx.accounts.minta.key(),CLMMError::InvalidTokenMint);
+        require!(pool.mintb==ctx.accounts.mintb.key(),CLMMError::InvalidTokenMint);
+        let position=&mut ctx.accounts.position;
+        // bring the position's fees up to date before paying them out
+        let (lower,upper)=load_position_ticks(pool,&ctx.accounts.tick_array_lower,&ctx.accounts.tick_array_upper,position)?;
+        let (fee_growth_inside_a,fee_growth_inside_b)=TickState::fee_growth_inside(&lower,&upper,pool.current_tick,pool.fee_growth_global_a,pool.fee_growth_global_b);
+        position.update(0,fee_growth_inside_a,fee_growth_inside_b)?;
+
+        let amounta=position.tokens_owed_a;
+        let amountb=position.tokens_owed_b;
+        position.tokens_owed_a=0;
+        position.tokens_owed_b=0;
+        let signer_seed=pool_signer_seeds!(pool);
+        if amounta!=0{
+            let account=TransferChecked{
+                from:ctx.accounts.vaulta.to_account_info(),
+                to:ctx.accounts.usertoken_account_a.to_account_info(),
+                authority:pool.to_account_info(),
+                mint:ctx.accounts.minta.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_a.to_account_info(), account, signer_seed);
+            transfer_checked(cpi_ctx, amounta, ctx.accounts.minta.decimals)?;
+        }
+        if amountb!=0{
+            let account=TransferChecked{
+                from:ctx.accounts.vault_b.to_account_info(),
+                to:ctx.accounts.usertoken_account_b.to_account_info(),
+                authority:pool.to_account_info(),
+                mint:ctx.accounts.mintb.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_b.to_account_info(), account, signer_seed);
+            transfer_checked(cpi_ctx, amountb, ctx.accounts.mintb.decimals)?;
+        }
+        emit_cpi!(FeesCollected{
+            pool:pool.key(),
+            position:ctx.accounts.position.key(),
+            amount_a:amounta,
+            amount_b:amountb,
+        });
+        Ok(())
+    }
+}
+
+/// Copies out the ticks bounding `position`, checking both arrays belong to
+/// the pool. The two loaders may point at the same account.
+pub fn load_position_ticks<'info>(
+    pool:&Account<'info,Pool>,
+    tick_array_lower:&AccountLoader<'info,TickArray>,
+    tick_array_upper:&AccountLoader<'info,TickArray>,
+    position:&Position,
+)->Result<(TickState,TickState)>{
+    let tick_spacing=pool.tick_spacing as i32;
+    let mut ticks:[Option<TickState>;2]=[None,None];
+    for (i,(loader,tick_index)) in [(tick_array_lower,position.tick_lower),(tick_array_upper,position.tick_upper)].into_iter().enumerate(){
+        let tick_array=loader.load()?;
+        require_keys_eq!(tick_array.pool,pool.key(),CLMMError::InvalidTickArray);
+        require!(tick_array.start_tick_index==tick_array_start_index(tick_index,tick_spacing),CLMMError::InvalidTickArray);
+        ticks[i]=Some(*tick_array.get_tick(tick_index,tick_spacing)?);
+    }
+    let [Some(lower),Some(upper)]=ticks else{
+        unreachable!()
+    };
+    Ok((lower,upper))
+}
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{error::CLMMError, state::{pool_signer_seeds, AmmConfig, Pool}};
+
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info>{
+    #[account(address=amm_config.admin @ CLMMError::Unauthorized)]
+    pub signer:Signer<'info>,
+    #[account(address=config.amm_config)]
+    pub amm_config:Account<'info,AmmConfig>,
+    #[account(address=config.minta @ CLMMError::InvalidTokenMint,mint::token_program=token_program_a)]
+    pub minta:InterfaceAccount<'info, Mint>,
+    #[account(address=config.mintb @ CLMMError::InvalidTokenMint,mint::token_program=token_program_b)]
+    pub mintb:InterfaceAccount<'info, Mint>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config)]
+    pub vaulta:InterfaceAccount<'info, TokenAccount>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config)]
+    pub  vault_b:InterfaceAccount<'info, TokenAccount>,
+    #[account(mut,token::mint=minta,token::token_program=token_program_a,token::authority=amm_config.treasury)]
+    pub treasury_token_account_a:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,token::mint=mintb,token::token_program=token_program_b,token::authority=amm_config.treasury)]
+    pub treasury_token_account_b:InterfaceAccount<'info,TokenAccount>,
+    pub token_program_a:Interface<'info, TokenInterface>,
+    pub token_program_b:Interface<'info, TokenInterface>,
+}
+impl <'info> CollectProtocolFees<'info>{
+    pub fn collect_protocol_fees(ctx:Context<CollectProtocolFees>)->Result<()>{
+        let pool=&mut ctx.accounts.config;
+        let amounta=pool.protocol_fees_owed_a;
+        let amountb=pool.protocol_fees_owed_b;
+        pool.protocol_fees_owed_a=0;
+        pool.protocol_fees_owed_b=0;
+        let signer_seed=pool_signer_seeds!(pool);
+        if amounta!=0{
+            let account=TransferChecked{
+                from:ctx.accounts.vaulta.to_account_info(),
+                to:ctx.accounts.treasury_token_account_a.to_account_info(),
+                authority:pool.to_account_info(),
+                mint:ctx.accounts.minta.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_a.to_account_info(), account, signer_seed);
+            transfer_checked(cpi_ctx, amounta, ctx.accounts.minta.decimals)?;
+        }
+        if amountb!=0{
+            let account=TransferChecked{
+                from:ctx.accounts.vault_b.to_account_info(),
+                to:ctx.accounts.treasury_token_account_b.to_account_info(),
+                authority:pool.to_account_info(),
+                mint:ctx.accounts.mintb.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_b.to_account_info(), account, signer_seed);
+            transfer_checked(cpi_ctx, amountb, ctx.accounts.mintb.decimals)?;
+        }
+        Ok(())
+    }
+}
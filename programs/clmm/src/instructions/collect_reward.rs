@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{error::CLMMError, instructions::load_position_ticks, state::{pool_signer_seeds, Pool, Position, TickArray, TickState}, utils::{block_timestamp, NUM_REWARDS}};
+
+#[derive(Accounts)]
+pub struct CollectReward<'info>{
+    #[account(mut)]
+    pub signer:Signer<'info>,
+    #[account(constraint=position_token_account.mint==position.position_mint && position_token_account.owner==signer.key() && position_token_account.amount==1 @ CLMMError::NotPositionOwner)]
+    pub position_token_account:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,seeds=[b"position",position.position_mint.as_ref()],bump=position.bump,constraint=position.pool==config.key() @ CLMMError::InvalidPosition)]
+    pub position:Account<'info,Position>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    /// may be the same account as `tick_array_upper` when both ticks share an array
+    pub tick_array_lower:AccountLoader<'info,TickArray>,
+    pub tick_array_upper:AccountLoader<'info,TickArray>,
+    #[account(mint::token_program=reward_token_program)]
+    pub reward_mint:InterfaceAccount<'info, Mint>,
+    #[account(mut,token::mint=reward_mint,token::authority=config,token::token_program=reward_token_program)]
+    pub reward_vault:InterfaceAccount<'info,TokenAccount>,
+    #[account(init_if_needed,associated_token::mint=reward_mint,associated_token::token_program=reward_token_program,associated_token::authority=signer,payer=signer)]
+    pub user_reward_account:InterfaceAccount<'info,TokenAccount>,
+    pub reward_token_program:Interface<'info, TokenInterface>,
+    pub system_program:Program<'info,System>,
+    pub associated_token_program:Program<'info,AssociatedToken>,
+}
+impl <'info> CollectReward<'info>{
+    /// Pays out what the position has earned of one reward, or as much of it
+    /// as the vault holds. The rest stays owed.
+    pub fn collect_reward(ctx:Context<CollectReward>,reward_index:u8)->Result<()>{
+        let pool=&mut ctx.accounts.config;
+        let index=reward_index as usize;
+        require!(index<NUM_REWARDS && pool.reward_infos[index].initialized(),CLMMError::InvalidRewardIndex);
+        require_keys_eq!(ctx.accounts.reward_mint.key(),pool.reward_infos[index].mint,CLMMError::InvalidTokenMint);
+        require_keys_eq!(ctx.accounts.reward_vault.key(),pool.reward_infos[index].vault,CLMMError::InvalidVault);
+        pool.update_rewards(block_timestamp()?)?;
+        let position=&mut ctx.accounts.position;
+        let (lower,upper)=load_position_ticks(pool,&ctx.accounts.tick_array_lower,&ctx.accounts.tick_array_upper,position)?;
+        position.update_rewards(TickState::reward_growths_inside(&lower,&upper,pool.current_tick,&pool.reward_growths_global()))?;
+
+        let amount=position.reward_infos[index].amount_owed.min(ctx.accounts.reward_vault.amount);
+        position.reward_infos[index].amount_owed-=amount;
+        if amount!=0{
+            let signer_seed=pool_signer_seeds!(pool);
+            let account=TransferChecked{
+                from:ctx.accounts.reward_vault.to_account_info(),
+                to:ctx.accounts.user_reward_account.to_account_info(),
+                authority:pool.to_account_info(),
+                mint:ctx.accounts.reward_mint.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.reward_token_program.to_account_info(), account, signer_seed);
+            transfer_checked(cpi_ctx, amount, ctx.accounts.reward_mint.decimals)?;
+        }
+        Ok(())
+    }
+}
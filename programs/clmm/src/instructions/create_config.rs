@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, program::Clmm, state::{AmmConfig, FeeTier}};
+
+#[derive(Accounts)]
+pub struct CreateConfig<'info>{
+    #[account(mut)]
+    pub signer:Signer<'info>,
+    #[account(init,seeds=[b"amm_config"],bump,payer=signer,space=8+AmmConfig::INIT_SPACE)]
+    pub amm_config:Account<'info,AmmConfig>,
+    /// only the program's upgrade authority may create the config
+    #[account(constraint=program.programdata_address()?==Some(program_data.key()) @ CLMMError::Unauthorized)]
+    pub program:Program<'info,Clmm>,
+    #[account(constraint=program_data.upgrade_authority_address==Some(signer.key()) @ CLMMError::Unauthorized)]
+    pub program_data:Account<'info,ProgramData>,
+    pub system_program:Program<'info,System>,
+}
+impl <'info> CreateConfig<'info>{
+    pub fn create_config(ctx:Context<CreateConfig>,treasury:Pubkey,fee_tiers:Vec<FeeTier>)->Result<()>{
+        AmmConfig::validate_fee_tiers(&fee_tiers)?;
+        let amm_config=&mut ctx.accounts.amm_config;
+        amm_config.admin=ctx.accounts.signer.key();
+        amm_config.pending_admin=Pubkey::default();
+        amm_config.treasury=treasury;
+        amm_config.fee_tiers=fee_tiers;
+        amm_config.bump=ctx.bumps.amm_config;
+        Ok(())
+    }
+}
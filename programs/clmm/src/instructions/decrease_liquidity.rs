@@ -0,0 +1,129 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{error::CLMMError, events::LiquidityDecreased, instructions::update_tick_arrays, mint_extensions::transfer_fee, native_sol::{is_native_mint, open_wsol, unwrap_sol}, state::{pool_signer_seeds, ObservationState, Pool, Position, TickArray, TickArrayBitmapExtension}, utils::{block_timestamp, calculate_liquidity_amounts, check_deadline, tick_to_sqrt_price_x64}};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DecreaseLiquidity<'info>{
+#[account(mut)]
+    pub signer:Signer<'info>,
+    #[account(mint::token_program=token_program_a)]
+    pub minta:InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program=token_program_b)]
+    pub mintb:InterfaceAccount<'info, Mint>,
+    #[account(init_if_needed,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=signer,payer=signer)]
+    pub usertoken_account_a:InterfaceAccount<'info,TokenAccount>,
+    #[account(init_if_needed,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=signer,payer=signer)]
+    pub usertoken_account_b:InterfaceAccount<'info,TokenAccount>,
+    /// CHECK: the signer's ephemeral wSOL account at `[b"wsol", signer]`, only used
+    /// with `native_sol` and checked against that address when it is opened
+    #[account(mut)]
+    pub wsol_account:Option<UncheckedAccount<'info>>,
+    #[account(constraint=position_token_account.mint==position.position_mint && position_token_account.owner==signer.key() && position_token_account.amount==1 @ CLMMError::NotPositionOwner)]
+    pub position_token_account:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,seeds=[b"position",position.position_mint.as_ref()],bump=position.bump,constraint=position.pool==config.key() @ CLMMError::InvalidPosition)]
+    pub position:Account<'info,Position>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config)]
+    pub vaulta:InterfaceAccount<'info, TokenAccount>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config)]
+    pub  vault_b:InterfaceAccount<'info, TokenAccount>,
+    /// may be the same account as `tick_array_upper` when both ticks share an array
+    #[account(mut)]
+    pub tick_array_lower:AccountLoader<'info,TickArray>,
+    #[account(mut)]
+    pub tick_array_upper:AccountLoader<'info,TickArray>,
+    #[account(mut,seeds=[b"tick_array_bitmap",config.key().as_ref()],bump)]
+    pub tick_array_bitmap:AccountLoader<'info,TickArrayBitmapExtension>,
+    #[account(mut,seeds=[b"observation",config.key().as_ref()],bump)]
+    pub observation:AccountLoader<'info,ObservationState>,
+    pub token_program_a:Interface<'info, TokenInterface>,
+    pub token_program_b:Interface<'info, TokenInterface>,
+    pub system_program:Program<'info,System>,
+    pub associated_token_program:Program<'info,AssociatedToken>,
+}
+impl <'info> DecreaseLiquidity<'info> {
+      /// With `native_sol` the native side is unwrapped and paid out as lamports.
+      pub fn decrease_liquidity(ctx:Context<DecreaseLiquidity>,liquidity:u128,token_min_a:u64,token_min_b:u64,native_sol:bool,deadline:Option<i64>)->Result<()>{
+        check_deadline(deadline)?;
+        require!(liquidity>0,CLMMError::ZeroAmount);
+        let  pool=&mut ctx.accounts.config;
+        let position=&mut ctx.accounts.position;
+        let (lower_tick,upper_tick)=(position.tick_lower,position.tick_upper);
+        require!(liquidity<=position.liquidity,CLMMError::InsufficientPositionLiquidity);
+        require!(pool.minta==ctx.accounts.minta.key(),CLMMError::InvalidTokenMint);
+        require!(pool.mintb==ctx.accounts.mintb.key(),CLMMError::InvalidTokenMint);
+        let (native_a,native_b)=(native_sol && is_native_mint(&ctx.accounts.minta),native_sol && is_native_mint(&ctx.accounts.mintb));
+        require!(!native_sol || native_a || native_b,CLMMError::NativeMintNotInPool);
+       let price_lower=tick_to_sqrt_price_x64(lower_tick)?;
+       let price_uperr=tick_to_sqrt_price_x64(upper_tick)?;
+       let (amounta,amountb)=calculate_liquidity_amounts(pool.sqrt_price, price_lower,price_uperr,liquidity,false)?;
+       // the minimums apply to what the signer receives after transfer fees
+       let received_a=amounta-transfer_fee(&ctx.accounts.minta,amounta)?;
+       let received_b=amountb-transfer_fee(&ctx.accounts.mintb,amountb)?;
+       require!(received_a>=token_min_a && received_b>=token_min_b,CLMMError::TokenMinNotMet);
+   let liquidity_delta:i128=liquidity.try_into().map_err(|_|CLMMError::ArithmeticOverflow)?;
+   let (fee_growth_inside_a,fee_growth_inside_b,reward_growths_inside)=update_tick_arrays(pool,&ctx.accounts.tick_array_bitmap,&ctx.accounts.tick_array_lower,&ctx.accounts.tick_array_upper,lower_tick,upper_tick,-liquidity_delta)?;
+   position.update_rewards(reward_growths_inside)?;
+   position.update(-liquidity_delta,fee_growth_inside_a,fee_growth_inside_b)?;
+   if lower_tick<=pool.current_tick && pool.current_tick<upper_tick{
+    // the oracle records the liquidity that held up to now before it changes
+    ctx.accounts.observation.load_mut()?.write(block_timestamp()?,pool.current_tick,pool.active_liqiudity);
+    pool.active_liqiudity=pool.active_liqiudity.checked_sub(liquidity).ok_or(CLMMError::ArithmeticOverflow)?;
+   }
+   let signer_seed=pool_signer_seeds!(pool);
+   // the native side is paid into an ephemeral wSOL account and unwrapped from there
+   let user_account_a=if native_a{
+    open_wsol(&ctx.accounts.signer,&ctx.accounts.wsol_account,&ctx.accounts.minta,0,&ctx.accounts.system_program,&ctx.accounts.token_program_a)?
+   }else{
+    ctx.accounts.usertoken_account_a.to_account_info()
+   };
+   let user_account_b=if native_b{
+    open_wsol(&ctx.accounts.signer,&ctx.accounts.wsol_account,&ctx.accounts.mintb,0,&ctx.accounts.system_program,&ctx.accounts.token_program_b)?
+   }else{
+    ctx.accounts.usertoken_account_b.to_account_info()
+   };
+   if amounta!=0{
+    let account=TransferChecked{
+        from:ctx.accounts.vaulta.to_account_info(),
+        to:user_account_a.clone(),
+        authority:pool.to_account_info(),
+        mint:ctx.accounts.minta.to_account_info()
+    };
+    let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_a.to_account_info(), account, signer_seed);
+    transfer_checked(cpi_ctx, amounta, ctx.accounts.minta.decimals)?;
+   }
+   if amountb!=0{
+    let account=TransferChecked{
+        from:ctx.accounts.vault_b.to_account_info(),
+        to:user_account_b.clone(),
+        authority:pool.to_account_info(),
+        mint:ctx.accounts.mintb.to_account_info()
+    };
+    let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_b.to_account_info(), account, signer_seed);
+    transfer_checked(cpi_ctx, amountb, ctx.accounts.mintb.decimals)?;
+   }
+   if native_a{
+    unwrap_sol(&ctx.accounts.signer,&user_account_a,&ctx.accounts.token_program_a)?;
+   }
+   if native_b{
+    unwrap_sol(&ctx.accounts.signer,&user_account_b,&ctx.accounts.token_program_b)?;
+   }
+   emit_cpi!(LiquidityDecreased{
+    pool:ctx.accounts.config.key(),
+    position:ctx.accounts.position.key(),
+    tick_lower:lower_tick,
+    tick_upper:upper_tick,
+    liquidity,
+    amount_a:amounta,
+    amount_b:amountb,
+   });
+
+Ok(())
+      }
+}
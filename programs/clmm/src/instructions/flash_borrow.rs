@@ -0,0 +1,90 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
+        sysvar::instructions::{self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked},
+    },
+    Discriminator,
+};
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{error::CLMMError, instruction::FlashRepay as FlashRepayInstruction, state::{pool_signer_seeds, Pool}};
+
+/// Position of `config` among the accounts of a `flash_repay` instruction
+const FLASH_REPAY_CONFIG_INDEX:usize=5;
+
+#[derive(Accounts)]
+pub struct FlashBorrow<'info>{
+    pub signer:Signer<'info>,
+    #[account(address=config.minta @ CLMMError::InvalidTokenMint,mint::token_program=token_program_a)]
+    pub minta:InterfaceAccount<'info, Mint>,
+    #[account(address=config.mintb @ CLMMError::InvalidTokenMint,mint::token_program=token_program_b)]
+    pub mintb:InterfaceAccount<'info, Mint>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=signer)]
+    pub usertoken_account_a:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=signer)]
+    pub usertoken_account_b:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config)]
+    pub vaulta:InterfaceAccount<'info, TokenAccount>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config)]
+    pub  vault_b:InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: the instructions sysvar, checked by address
+    #[account(address=instructions_sysvar::ID)]
+    pub instructions:UncheckedAccount<'info>,
+    pub token_program_a:Interface<'info, TokenInterface>,
+    pub token_program_b:Interface<'info, TokenInterface>,
+}
+impl <'info> FlashBorrow<'info>{
+    /// Lends `amount_a` and `amount_b` out of the vaults until the `flash_repay`
+    /// that has to come later in the same transaction. The pool is locked in
+    /// between, so the loan cannot be used against the pool itself.
+    pub fn flash_borrow(ctx:Context<FlashBorrow>,amount_a:u64,amount_b:u64)->Result<()>{
+        require!(amount_a>0 || amount_b>0,CLMMError::ZeroAmount);
+        // through CPI the current instruction is the caller's, and the repay search below would mean nothing
+        require!(get_stack_height()==TRANSACTION_LEVEL_STACK_HEIGHT,CLMMError::FlashBorrowCpi);
+        let instructions=ctx.accounts.instructions.to_account_info();
+        let current_index=load_current_index_checked(&instructions)? as usize;
+        let pool_key=ctx.accounts.config.key();
+        let mut index=current_index+1;
+        let repaid=loop{
+            let Ok(instruction)=load_instruction_at_checked(index,&instructions) else{
+                break false;
+            };
+            // only the repay's own config account counts, not the same pool passed along as an extra account
+            if instruction.program_id==crate::ID && instruction.data.starts_with(FlashRepayInstruction::DISCRIMINATOR) && instruction.accounts.get(FLASH_REPAY_CONFIG_INDEX).map(|meta|meta.pubkey)==Some(pool_key){
+                break true;
+            }
+            index+=1;
+        };
+        require!(repaid,CLMMError::MissingFlashRepay);
+
+        let pool=&mut ctx.accounts.config;
+        pool.locked=true;
+        pool.flash_loan_amount_a=amount_a;
+        pool.flash_loan_amount_b=amount_b;
+        let signer_seed=pool_signer_seeds!(pool);
+        if amount_a!=0{
+            let account=TransferChecked{
+                from:ctx.accounts.vaulta.to_account_info(),
+                to:ctx.accounts.usertoken_account_a.to_account_info(),
+                authority:pool.to_account_info(),
+                mint:ctx.accounts.minta.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_a.to_account_info(), account, signer_seed);
+            transfer_checked(cpi_ctx, amount_a, ctx.accounts.minta.decimals)?;
+        }
+        if amount_b!=0{
+            let account=TransferChecked{
+                from:ctx.accounts.vault_b.to_account_info(),
+                to:ctx.accounts.usertoken_account_b.to_account_info(),
+                authority:pool.to_account_info(),
+                mint:ctx.accounts.mintb.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_b.to_account_info(), account, signer_seed);
+            transfer_checked(cpi_ctx, amount_b, ctx.accounts.mintb.decimals)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{error::CLMMError, math::mul_div_ceil, mint_extensions::transfer_inverse_fee, state::Pool, utils::FEE_RATE_DENOMINATOR};
+
+#[derive(Accounts)]
+pub struct FlashRepay<'info>{
+    pub signer:Signer<'info>,
+    #[account(address=config.minta @ CLMMError::InvalidTokenMint,mint::token_program=token_program_a)]
+    pub minta:InterfaceAccount<'info, Mint>,
+    #[account(address=config.mintb @ CLMMError::InvalidTokenMint,mint::token_program=token_program_b)]
+    pub mintb:InterfaceAccount<'info, Mint>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=signer)]
+    pub usertoken_account_a:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=signer)]
+    pub usertoken_account_b:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config)]
+    pub vaulta:InterfaceAccount<'info, TokenAccount>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config)]
+    pub  vault_b:InterfaceAccount<'info, TokenAccount>,
+    pub token_program_a:Interface<'info, TokenInterface>,
+    pub token_program_b:Interface<'info, TokenInterface>,
+}
+impl <'info> FlashRepay<'info>{
+    /// Pays back the outstanding flash loan plus the pool's swap fee on each
+    /// amount, shared out like a swap fee, and unlocks the pool.
+    pub fn flash_repay(ctx:Context<FlashRepay>)->Result<()>{
+        let pool=&mut ctx.accounts.config;
+        // a flash swap locks the pool too, but only a borrow leaves amounts to repay
+        require!(pool.locked && (pool.flash_loan_amount_a>0 || pool.flash_loan_amount_b>0),CLMMError::NoActiveFlashLoan);
+        let (amount_a,amount_b)=(pool.flash_loan_amount_a,pool.flash_loan_amount_b);
+        let fee_a=mul_div_ceil(amount_a as u128,pool.fee_rate as u128,FEE_RATE_DENOMINATOR as u128)? as u64;
+        let fee_b=mul_div_ceil(amount_b as u128,pool.fee_rate as u128,FEE_RATE_DENOMINATOR as u128)? as u64;
+        pool.credit_fee(fee_a,true)?;
+        pool.credit_fee(fee_b,false)?;
+        pool.locked=false;
+        pool.flash_loan_amount_a=0;
+        pool.flash_loan_amount_b=0;
+
+        // the vaults get back the loan and fee in full, transfer fees are on the signer
+        let repay_a=amount_a.checked_add(fee_a).ok_or(CLMMError::ArithmeticOverflow)?;
+        let repay_b=amount_b.checked_add(fee_b).ok_or(CLMMError::ArithmeticOverflow)?;
+        if repay_a!=0{
+            let repay_a=repay_a.checked_add(transfer_inverse_fee(&ctx.accounts.minta,repay_a)?).ok_or(CLMMError::ArithmeticOverflow)?;
+            let account=TransferChecked{
+                from:ctx.accounts.usertoken_account_a.to_account_info(),
+                to:ctx.accounts.vaulta.to_account_info(),
+                authority:ctx.accounts.signer.to_account_info(),
+                mint:ctx.accounts.minta.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new(ctx.accounts.token_program_a.to_account_info(), account);
+            transfer_checked(cpi_ctx, repay_a, ctx.accounts.minta.decimals)?;
+        }
+        if repay_b!=0{
+            let repay_b=repay_b.checked_add(transfer_inverse_fee(&ctx.accounts.mintb,repay_b)?).ok_or(CLMMError::ArithmeticOverflow)?;
+            let account=TransferChecked{
+                from:ctx.accounts.usertoken_account_b.to_account_info(),
+                to:ctx.accounts.vault_b.to_account_info(),
+                authority:ctx.accounts.signer.to_account_info(),
+                mint:ctx.accounts.mintb.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new(ctx.accounts.token_program_b.to_account_info(), account);
+            transfer_checked(cpi_ctx, repay_b, ctx.accounts.mintb.decimals)?;
+        }
+        Ok(())
+    }
+}
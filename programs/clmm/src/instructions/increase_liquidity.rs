@@ -0,0 +1,210 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface,TransferChecked,transfer_checked},
+};
+
+use crate::{error::CLMMError, events::LiquidityIncreased, mint_extensions::{transfer_fee, transfer_inverse_fee}, native_sol::{is_native_mint, open_wsol, unwrap_sol}, state::{ObservationState, Pool, Position, TickArray, TickArrayBitmapExtension, TickState}, utils::{block_timestamp, calculate_liquidity_amounts, check_deadline, flip_tick_array_bit, get_liquidity_for_amount_a, get_liquidity_for_amount_b, get_liquidity_for_amounts, tick_array_start_index, tick_to_sqrt_price_x64, NUM_REWARDS}};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct IncreaseLiquidity<'info>{
+#[account(mut)]
+    pub signer:Signer<'info>,
+    #[account(mint::token_program=token_program_a)]
+    pub minta:InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program=token_program_b)]
+    pub mintb:InterfaceAccount<'info, Mint>,
+    #[account(init_if_needed,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=signer,payer=signer)]
+    pub usertoken_account_a:InterfaceAccount<'info,TokenAccount>,
+    #[account(init_if_needed,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=signer,payer=signer)]
+    pub usertoken_account_b:InterfaceAccount<'info,TokenAccount>,
+    /// CHECK: the signer's ephemeral wSOL account at `[b"wsol", signer]`, only used
+    /// with `native_sol` and checked against that address when it is opened
+    #[account(mut)]
+    pub wsol_account:Option<UncheckedAccount<'info>>,
+    #[account(constraint=position_token_account.mint==position.position_mint && position_token_account.owner==signer.key() && position_token_account.amount==1 @ CLMMError::NotPositionOwner)]
+    pub position_token_account:InterfaceAccount<'info,TokenAccount>,
+    #[account(mut,seeds=[b"position",position.position_mint.as_ref()],bump=position.bump,constraint=position.pool==config.key() @ CLMMError::InvalidPosition)]
+    pub position:Account<'info,Position>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config)]
+    pub vaulta:InterfaceAccount<'info, TokenAccount>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config)]
+    pub  vault_b:InterfaceAccount<'info, TokenAccount>,
+    /// may be the same account as `tick_array_upper` when both ticks share an array
+    #[account(mut)]
+    pub tick_array_lower:AccountLoader<'info,TickArray>,
+    #[account(mut)]
+    pub tick_array_upper:AccountLoader<'info,TickArray>,
+    #[account(mut,seeds=[b"tick_array_bitmap",config.key().as_ref()],bump)]
+    pub tick_array_bitmap:AccountLoader<'info,TickArrayBitmapExtension>,
+    #[account(mut,seeds=[b"observation",config.key().as_ref()],bump)]
+    pub observation:AccountLoader<'info,ObservationState>,
+    pub token_program_a:Interface<'info, TokenInterface>,
+    pub token_program_b:Interface<'info, TokenInterface>,
+    pub system_program:Program<'info,System>,
+    pub associated_token_program:Program<'info,AssociatedToken>,
+}
+impl <'info> IncreaseLiquidity<'info> {
+      pub fn increase_liquidity(ctx:Context<IncreaseLiquidity>,liquidity:u128,token_max_a:u64,token_max_b:u64,native_sol:bool,deadline:Option<i64>)->Result<()>{
+        check_deadline(deadline)?;
+        require!(liquidity>0,CLMMError::ZeroAmount);
+        let event=ctx.accounts.deposit(liquidity,token_max_a,token_max_b,native_sol)?;
+        emit_cpi!(event);
+Ok(())
+      }
+
+      /// Deposits the most liquidity `amount_a_max` and `amount_b_max` can pay for.
+      /// With a `base_token_a` of `Some`, liquidity is sized from that token
+      /// alone and the other one only has to stay within its maximum.
+      pub fn increase_liquidity_by_amounts(ctx:Context<IncreaseLiquidity>,amount_a_max:u64,amount_b_max:u64,base_token_a:Option<bool>,native_sol:bool,deadline:Option<i64>)->Result<()>{
+        check_deadline(deadline)?;
+        let pool=&ctx.accounts.config;
+        let position=&ctx.accounts.position;
+        // size liquidity on what reaches the vaults once transfer fees are withheld
+        let amount_a=amount_a_max-transfer_fee(&ctx.accounts.minta,amount_a_max)?;
+        let amount_b=amount_b_max-transfer_fee(&ctx.accounts.mintb,amount_b_max)?;
+        let price_lower=tick_to_sqrt_price_x64(position.tick_lower)?;
+        let price_uperr=tick_to_sqrt_price_x64(position.tick_upper)?;
+        let liquidity=match base_token_a{
+            None=>get_liquidity_for_amounts(pool.sqrt_price,price_lower,price_uperr,amount_a,amount_b)?,
+            Some(true)=>{
+                // token A is only deposited while the price is below the upper tick
+                require!(pool.sqrt_price<price_uperr,CLMMError::InvalidLiquidity);
+                get_liquidity_for_amount_a(pool.sqrt_price.max(price_lower),price_uperr,amount_a)?
+            }
+            Some(false)=>{
+                // token B is only deposited while the price is above the lower tick
+                require!(pool.sqrt_price>price_lower,CLMMError::InvalidLiquidity);
+                get_liquidity_for_amount_b(price_lower,pool.sqrt_price.min(price_uperr),amount_b)?
+            }
+        };
+        require!(liquidity>0,CLMMError::ZeroAmount);
+        let event=ctx.accounts.deposit(liquidity,amount_a_max,amount_b_max,native_sol)?;
+        emit_cpi!(event);
+Ok(())
+      }
+
+      /// Adds `liquidity` to the position and pulls the tokens backing it from
+      /// the signer, as long as neither amount is over its maximum. The maximums
+      /// cover any transfer fee the mints charge on top of the deposit. With
+      /// `native_sol` the native side is paid in lamports, through an ephemeral
+      /// wSOL account that is closed again before returning.
+      /// Returns the event describing the deposit for the caller to emit.
+      fn deposit(&mut self,liquidity:u128,token_max_a:u64,token_max_b:u64,native_sol:bool)->Result<LiquidityIncreased>{
+        let  pool=&mut self.config;
+        let position=&mut self.position;
+        let (lower_tick,upper_tick)=(position.tick_lower,position.tick_upper);
+        require!(pool.minta==self.minta.key(),CLMMError::InvalidTokenMint);
+        require!(pool.mintb==self.mintb.key(),CLMMError::InvalidTokenMint);
+        let liquidity_delta:i128=liquidity.try_into().map_err(|_|CLMMError::ArithmeticOverflow)?;
+        let (fee_growth_inside_a,fee_growth_inside_b,reward_growths_inside)=update_tick_arrays(pool,&self.tick_array_bitmap,&self.tick_array_lower,&self.tick_array_upper,lower_tick,upper_tick,liquidity_delta)?;
+        position.update_rewards(reward_growths_inside)?;
+        position.update(liquidity_delta,fee_growth_inside_a,fee_growth_inside_b)?;
+        if lower_tick<=pool.current_tick && pool.current_tick<upper_tick{
+            // the oracle records the liquidity that held up to now before it changes
+            self.observation.load_mut()?.write(block_timestamp()?,pool.current_tick,pool.active_liqiudity);
+            pool.active_liqiudity=pool.active_liqiudity.checked_add(liquidity).ok_or(CLMMError::ArithmeticOverflow)?;
+        }
+       let price_lower=tick_to_sqrt_price_x64(lower_tick)?;
+       let price_uperr=tick_to_sqrt_price_x64(upper_tick)?;
+       let (amounta,amountb)=calculate_liquidity_amounts(pool.sqrt_price, price_lower,price_uperr,liquidity,true)?;
+       let amounta=amounta.checked_add(transfer_inverse_fee(&self.minta,amounta)?).ok_or(CLMMError::ArithmeticOverflow)?;
+       let amountb=amountb.checked_add(transfer_inverse_fee(&self.mintb,amountb)?).ok_or(CLMMError::ArithmeticOverflow)?;
+       require!(amounta<=token_max_a && amountb<=token_max_b,CLMMError::TokenMaxExceeded);
+       let (native_a,native_b)=(native_sol && is_native_mint(&self.minta),native_sol && is_native_mint(&self.mintb));
+       require!(!native_sol || native_a || native_b,CLMMError::NativeMintNotInPool);
+       let user_account_a=if native_a{
+           open_wsol(&self.signer,&self.wsol_account,&self.minta,amounta,&self.system_program,&self.token_program_a)?
+       }else{
+           self.usertoken_account_a.to_account_info()
+       };
+       let user_account_b=if native_b{
+           open_wsol(&self.signer,&self.wsol_account,&self.mintb,amountb,&self.system_program,&self.token_program_b)?
+       }else{
+           self.usertoken_account_b.to_account_info()
+       };
+    if amounta!=0{
+        let account=TransferChecked{
+            from:user_account_a.clone(),
+            to:self.vaulta.to_account_info(),
+            mint:self.minta.to_account_info(),
+            authority:self.signer.to_account_info()
+        };
+        let cpicontext=CpiContext::new(self.token_program_a.to_account_info(), account);
+    transfer_checked(cpicontext, amounta, self.minta.decimals)?;
+    }
+    if amountb!=0{
+        let account=TransferChecked{
+            from:user_account_b.clone(),
+            to:self.vault_b.to_account_info(),
+            mint:self.mintb.to_account_info(),
+            authority:self.signer.to_account_info()
+        };
+        let cpicontext=CpiContext::new(self.token_program_b.to_account_info(), account);
+    transfer_checked(cpicontext, amountb, self.mintb.decimals)?;
+    }
+    if native_a{
+        unwrap_sol(&self.signer,&user_account_a,&self.token_program_a)?;
+    }
+    if native_b{
+        unwrap_sol(&self.signer,&user_account_b,&self.token_program_b)?;
+    }
+Ok(LiquidityIncreased{
+    pool:self.config.key(),
+    position:self.position.key(),
+    tick_lower:lower_tick,
+    tick_upper:upper_tick,
+    liquidity,
+    amount_a:amounta,
+    amount_b:amountb,
+})
+      }
+}
+
+/// Applies `liquidity_delta` to the lower and upper ticks of a range, keeping
+/// each array's initialized tick count and the pool bitmap and its extension in sync, and
+/// returns the range's fee and reward growth inside. Both loaders may point at the same
+/// account, each one is borrowed in turn.
+pub fn update_tick_arrays<'info>(
+    pool:&mut Account<'info,Pool>,
+    tick_array_bitmap:&AccountLoader<'info,TickArrayBitmapExtension>,
+    tick_array_lower:&AccountLoader<'info,TickArray>,
+    tick_array_upper:&AccountLoader<'info,TickArray>,
+    lower_tick:i32,
+    upper_tick:i32,
+    liquidity_delta:i128,
+)->Result<(u128,u128,[u128;NUM_REWARDS])>{
+    // rewards accrue to the liquidity in range before this change
+    pool.update_rewards(block_timestamp()?)?;
+    let reward_growths_global=pool.reward_growths_global();
+    let pool_key=pool.key();
+    let tick_spacing=pool.tick_spacing as i32;
+    let mut updated:[Option<TickState>;2]=[None,None];
+    for (i,(loader,tick_index,upper)) in [(tick_array_lower,lower_tick,false),(tick_array_upper,upper_tick,true)].into_iter().enumerate(){
+        let mut tick_array=loader.load_mut()?;
+        require_keys_eq!(tick_array.pool,pool_key,CLMMError::InvalidTickArray);
+        require!(tick_array.start_tick_index==tick_array_start_index(tick_index,tick_spacing),CLMMError::InvalidTickArray);
+        let tick=tick_array.get_tick_mut(tick_index,tick_spacing)?;
+        let flipped=tick.update(liquidity_delta,upper,pool.current_tick,pool.fee_growth_global_a,pool.fee_growth_global_b,&reward_growths_global)?;
+        updated[i]=Some(*tick);
+        if flipped{
+            let count_before=tick_array.initialized_tick_count;
+            tick_array.initialized_tick_count=if liquidity_delta>0{
+                count_before.checked_add(1)
+            }else{
+                count_before.checked_sub(1)
+            }.ok_or(CLMMError::ArithmeticOverflow)?;
+            if (count_before==0)!=(tick_array.initialized_tick_count==0){
+                flip_tick_array_bit(&mut pool.tick_array_bitmap,&mut *tick_array_bitmap.load_mut()?,tick_array.start_tick_index,tick_spacing)?;
+            }
+        }
+    }
+    let [Some(lower),Some(upper)]=updated else{
+        unreachable!()
+    };
+    let (fee_growth_inside_a,fee_growth_inside_b)=TickState::fee_growth_inside(&lower,&upper,pool.current_tick,pool.fee_growth_global_a,pool.fee_growth_global_b);
+    Ok((fee_growth_inside_a,fee_growth_inside_b,TickState::reward_growths_inside(&lower,&upper,pool.current_tick,&reward_growths_global)))
+}
@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{ObservationState, Pool};
+
+#[derive(Accounts)]
+pub struct IncreaseObservationCardinality<'info>{
+    pub signer:Signer<'info>,
+    #[account(seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump)]
+    pub config:Account<'info,Pool>,
+    #[account(mut,seeds=[b"observation",config.key().as_ref()],bump)]
+    pub observation:AccountLoader<'info,ObservationState>,
+}
+impl <'info> IncreaseObservationCardinality<'info>{
+    /// Lets the pool keep up to `cardinality_next` observations, widening how
+    /// far back `observe` can look. The buffer fills the new entries as swaps
+    /// come in, so the longer window is only usable after that many writes.
+    pub fn increase_observation_cardinality(ctx:Context<IncreaseObservationCardinality>,cardinality_next:u16)->Result<()>{
+        ctx.accounts.observation.load_mut()?.grow(cardinality_next)?;
+Ok(())
+    }
+}
@@ -4,42 +4,79 @@ use anchor_spl::{
     token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::{state::Pool, utils::{price_to_sqrt_price_x64, sqrt_price_x64_to_tick}};
+use crate::{error::CLMMError, events::PoolCreated, mint_extensions::validate_mint_extensions, state::{AmmConfig, InitialPrice, ObservationState, Pool, TickArrayBitmapExtension}, utils::{block_timestamp, price_to_sqrt_price_x64, sqrt_price_x64_to_tick, MAX_SQRT_PRICE_X64, MIN_SQRT_PRICE_X64}};
 
+#[event_cpi]
 #[derive(Accounts)]
-#[instruction(seeds:u64)]
+#[instruction(initial_price:InitialPrice,fee_rate:u32)]
 pub struct InitializePool<'info>{
 #[account(mut)]
 pub signer:Signer<'info>,
+#[account(seeds=[b"amm_config"],bump=amm_config.bump)]
+pub amm_config:Account<'info,AmmConfig>,
+#[account(mint::token_program=token_program_a)]
 pub minta:InterfaceAccount<'info, Mint>,
+#[account(mint::token_program=token_program_b)]
 pub mintb:InterfaceAccount<'info, Mint>,
-#[account(init,seeds=[b"lp",config.key().as_ref()],bump,payer=signer,mint::decimals=6,mint::authority=config)]
-pub lp_mint:InterfaceAccount<'info, Mint>,
-#[account(init,associated_token::mint=minta,associated_token::authority=config,payer=signer)]
+#[account(init,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config,payer=signer)]
 pub vaulta:InterfaceAccount<'info, TokenAccount>,
-#[account(init,seeds=[b"config",seeds.to_le_bytes().as_ref()],bump,payer=signer,space=8+Pool::INIT_SPACE)]
+#[account(init,seeds=[b"pool",amm_config.key().as_ref(),minta.key().as_ref(),mintb.key().as_ref(),fee_rate.to_le_bytes().as_ref()],bump,payer=signer,space=8+Pool::INIT_SPACE)]
 pub config:Account<'info,Pool>,
-#[account(init,associated_token::mint=mintb,associated_token::authority=config,payer=signer)]
+#[account(init,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config,payer=signer)]
 pub  vault_b:InterfaceAccount<'info, TokenAccount>,
+#[account(init,seeds=[b"observation",config.key().as_ref()],bump,payer=signer,space=8+ObservationState::INIT_SPACE)]
+pub observation:AccountLoader<'info,ObservationState>,
+#[account(init,seeds=[b"tick_array_bitmap",config.key().as_ref()],bump,payer=signer,space=8+TickArrayBitmapExtension::INIT_SPACE)]
+pub tick_array_bitmap:AccountLoader<'info,TickArrayBitmapExtension>,
 pub system_program:Program<'info,System>,
-pub token_program:Interface<'info, TokenInterface>,
+pub token_program_a:Interface<'info, TokenInterface>,
+pub token_program_b:Interface<'info, TokenInterface>,
 pub associated_token_program:Program<'info,AssociatedToken>
 }
 impl <'info> InitializePool<'info> {
-      pub fn initializepool(ctx:Context<InitializePool>,price:u64,seed:u64)->Result<()>{
-             let curr_sqrt_price=price_to_sqrt_price_x64(price)?;
-             let current_tick=sqrt_price_x64_to_tick(curr_sqrt_price);
+      pub fn initializepool(ctx:Context<InitializePool>,initial_price:InitialPrice,fee_rate:u32)->Result<()>{
+             // one pool per pair and fee tier, with the mints in a fixed order
+             require_keys_neq!(ctx.accounts.minta.key(),ctx.accounts.mintb.key(),CLMMError::SameTokenMint);
+             require!(ctx.accounts.minta.key()<ctx.accounts.mintb.key(),CLMMError::InvalidMintOrder);
+             validate_mint_extensions(&ctx.accounts.minta)?;
+             validate_mint_extensions(&ctx.accounts.mintb)?;
+             let tier=*ctx.accounts.amm_config.fee_tier(fee_rate)?;
+             let curr_sqrt_price=match initial_price{
+                 InitialPrice::SqrtPriceX64(sqrt_price)=>{
+                     require!((MIN_SQRT_PRICE_X64..MAX_SQRT_PRICE_X64).contains(&sqrt_price),CLMMError::SqrtPriceX64);
+                     sqrt_price
+                 }
+                 InitialPrice::Ratio{numerator,denominator}=>{
+                     price_to_sqrt_price_x64(numerator,denominator,ctx.accounts.minta.decimals,ctx.accounts.mintb.decimals)?
+                 }
+             };
+             let current_tick=sqrt_price_x64_to_tick(curr_sqrt_price)?;
              let  pool=&mut ctx.accounts.config;
              pool.minta=ctx.accounts.minta.key();
              pool.mintb=ctx.accounts.mintb.key();
-             pool.lp_mint=ctx.accounts.lp_mint.key();
+             pool.amm_config=ctx.accounts.amm_config.key();
               pool.bump=ctx.bumps.config;
-              pool.total_lp_issued=0;
               pool.active_liqiudity=0;
-              pool.current_tick=current_tick.unwrap();
+              pool.current_tick=current_tick;
               pool.active_liqiudity=0;
               pool.sqrt_price=curr_sqrt_price;
-              pool.seed=seed;
+              pool.fee_rate=tier.fee_rate;
+              pool.protocol_fee_rate=tier.protocol_fee_rate;
+              pool.tick_spacing=tier.tick_spacing;
+              let now=block_timestamp()?;
+              pool.reward_last_updated_timestamp=now;
+              ctx.accounts.observation.load_init()?.initialize(pool.key(),now);
+              ctx.accounts.tick_array_bitmap.load_init()?.pool=pool.key();
+              emit_cpi!(PoolCreated{
+                  pool:pool.key(),
+                  amm_config:pool.amm_config,
+                  minta:pool.minta,
+                  mintb:pool.mintb,
+                  fee_rate:pool.fee_rate,
+                  tick_spacing:pool.tick_spacing,
+                  sqrt_price:pool.sqrt_price,
+                  tick:pool.current_tick,
+              });
              Ok(())
 
       }
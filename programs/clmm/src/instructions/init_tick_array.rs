@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+use crate::{events::TickInitialized, state::{Pool, TickArray}, utils::check_tick_array_start_index};
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(start_tick_index:i32)]
+pub struct InitialTickArray<'info>{
+   #[account(mut)]
+    pub signer:Signer<'info>,
+    #[account(seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump)]
+    pub config:Account<'info,Pool>,
+    #[account(init,seeds=[b"tick_array",config.key().as_ref(),start_tick_index.to_le_bytes().as_ref()],bump,space=8+TickArray::INIT_SPACE,payer=signer)]
+    pub tick_array:AccountLoader<'info,TickArray>,
+    pub system_program:Program<'info,System>
+}
+impl <'info> InitialTickArray<'info>{
+    pub fn initializetickarray(ctx:Context<InitialTickArray>,start_tick_index:i32)->Result<()>{
+        let tick_spacing=ctx.accounts.config.tick_spacing as i32;
+        check_tick_array_start_index(start_tick_index,tick_spacing)?;
+        let mut tick_array=ctx.accounts.tick_array.load_init()?;
+        tick_array.pool=ctx.accounts.config.key();
+        tick_array.start_tick_index=start_tick_index;
+        for (i,tick) in tick_array.ticks.iter_mut().enumerate(){
+            tick.tick=start_tick_index+i as i32*tick_spacing;
+        }
+        emit_cpi!(TickInitialized{
+            pool:ctx.accounts.config.key(),
+            tick_array:ctx.accounts.tick_array.key(),
+            start_tick_index,
+        });
+Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{error::CLMMError, mint_extensions::validate_mint_extensions, state::{AmmConfig, Pool, RewardInfo}, utils::{block_timestamp, NUM_REWARDS}};
+
+#[derive(Accounts)]
+pub struct InitializeReward<'info>{
+    #[account(mut,address=amm_config.admin @ CLMMError::Unauthorized)]
+    pub signer:Signer<'info>,
+    #[account(address=config.amm_config)]
+    pub amm_config:Account<'info,AmmConfig>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    #[account(mint::token_program=reward_token_program)]
+    pub reward_mint:InterfaceAccount<'info, Mint>,
+    #[account(init,seeds=[b"reward_vault",config.key().as_ref(),reward_mint.key().as_ref()],bump,payer=signer,token::mint=reward_mint,token::authority=config,token::token_program=reward_token_program)]
+    pub reward_vault:InterfaceAccount<'info,TokenAccount>,
+    pub reward_token_program:Interface<'info, TokenInterface>,
+    pub system_program:Program<'info,System>,
+}
+impl <'info> InitializeReward<'info>{
+    /// Fills the next free reward slot with `reward_mint`. Nothing is emitted
+    /// until `authority` funds the vault and sets a rate.
+    pub fn initialize_reward(ctx:Context<InitializeReward>,reward_index:u8,authority:Pubkey)->Result<()>{
+        validate_mint_extensions(&ctx.accounts.reward_mint)?;
+        let pool=&mut ctx.accounts.config;
+        let index=reward_index as usize;
+        require!(index<NUM_REWARDS,CLMMError::InvalidRewardIndex);
+        // slots are used in order so a reward can be found by counting
+        require!(pool.reward_infos[..index].iter().all(|reward|reward.initialized()) && !pool.reward_infos[index].initialized(),CLMMError::InvalidRewardIndex);
+        pool.update_rewards(block_timestamp()?)?;
+        pool.reward_infos[index]=RewardInfo{
+            mint:ctx.accounts.reward_mint.key(),
+            vault:ctx.accounts.reward_vault.key(),
+            authority,
+            emissions_per_second_x64:0,
+            growth_global_x64:0,
+        };
+Ok(())
+    }
+}
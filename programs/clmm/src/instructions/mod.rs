@@ -1,11 +1,43 @@
-pub mod init_Tick;
+pub mod create_config;
+pub mod update_config;
+pub mod transfer_admin;
+pub mod accept_admin;
+pub mod init_tick_array;
 pub mod init_pool;
-pub mod add_liqiudity;
-pub mod withdraw;
+pub mod open_position;
+pub mod increase_liquidity;
+pub mod decrease_liquidity;
+pub mod close_position;
+pub mod collect_fees;
+pub mod collect_protocol_fees;
 pub mod swap;
+pub mod swap_route;
+pub mod increase_observation_cardinality;
+pub mod observe;
+pub mod initialize_reward;
+pub mod set_reward_emissions;
+pub mod collect_reward;
+pub mod flash_borrow;
+pub mod flash_repay;
+pub use create_config::*;
+pub use update_config::*;
+pub use transfer_admin::*;
+pub use accept_admin::*;
 pub  use init_pool::*;
-pub use init_Tick::*;
-pub use add_liqiudity::*;
-pub use withdraw::*;
+pub use init_tick_array::*;
+pub use open_position::*;
+pub use increase_liquidity::*;
+pub use decrease_liquidity::*;
+pub use close_position::*;
+pub use collect_fees::*;
+pub use collect_protocol_fees::*;
 pub use swap::*;
+pub use swap_route::*;
+pub use increase_observation_cardinality::*;
+pub use observe::*;
+pub use initialize_reward::*;
+pub use set_reward_emissions::*;
+pub use collect_reward::*;
+pub use flash_borrow::*;
+pub use flash_repay::*;
 
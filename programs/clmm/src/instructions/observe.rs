@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, state::{ObservationState, ObserveResult, Pool}, utils::{block_timestamp, MAX_OBSERVE_LEN}};
+
+#[derive(Accounts)]
+pub struct Observe<'info>{
+    #[account(seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump)]
+    pub config:Account<'info,Pool>,
+    #[account(seeds=[b"observation",config.key().as_ref()],bump)]
+    pub observation:AccountLoader<'info,ObservationState>,
+}
+impl <'info> Observe<'info>{
+    /// Cumulative tick and seconds per liquidity as of each `seconds_agos` ago,
+    /// returned through the transaction's return data. Callers average over a
+    /// window by differencing two entries.
+    pub fn observe(ctx:Context<Observe>,seconds_agos:Vec<u32>)->Result<ObserveResult>{
+        require!(seconds_agos.len()<=MAX_OBSERVE_LEN,CLMMError::TooManyObservations);
+        let pool=&ctx.accounts.config;
+        ctx.accounts.observation.load()?.observe(block_timestamp()?,&seconds_agos,pool.current_tick,pool.active_liqiudity)
+    }
+}
@@ -0,0 +1,100 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{create_account, CreateAccount},
+};
+use anchor_spl::{
+    associated_token::{create, get_associated_token_address_with_program_id, AssociatedToken, Create},
+    token_interface::{
+        initialize_mint2, mint_close_authority_initialize, mint_to, set_authority,
+        spl_token_2022::{self, extension::ExtensionType, instruction::AuthorityType},
+        InitializeMint2, MintCloseAuthorityInitialize, MintTo, SetAuthority, TokenInterface,
+    },
+};
+
+use crate::{error::CLMMError, state::{pool_signer_seeds, Pool, Position}, utils::{MAX_TICK, MIN_TICK}};
+
+#[derive(Accounts)]
+pub struct OpenPosition<'info>{
+    #[account(mut)]
+    pub signer:Signer<'info>,
+    #[account(seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump)]
+    pub config:Account<'info,Pool>,
+    /// created here, with no account behind it yet
+    #[account(mut)]
+    pub position_mint:Signer<'info>,
+    /// CHECK: the signer's associated token account for `position_mint`, created here once the mint exists
+    #[account(mut,address=get_associated_token_address_with_program_id(&signer.key(),&position_mint.key(),&token_program.key()))]
+    pub position_token_account:UncheckedAccount<'info>,
+    #[account(init,seeds=[b"position",position_mint.key().as_ref()],bump,payer=signer,space=8+Position::INIT_SPACE)]
+    pub position:Account<'info,Position>,
+    pub system_program:Program<'info,System>,
+    pub token_program:Interface<'info, TokenInterface>,
+    pub associated_token_program:Program<'info,AssociatedToken>
+}
+impl <'info> OpenPosition<'info>{
+    pub fn open_position(ctx:Context<OpenPosition>,tick_lower:i32,tick_upper:i32)->Result<()>{
+        require!(tick_upper>tick_lower,CLMMError::TickMismatch);
+        require!(tick_lower>=MIN_TICK,CLMMError::TickLowerOverflow);
+        require!(tick_upper<=MAX_TICK,CLMMError::TickUpperOverflow);
+        let tick_spacing=ctx.accounts.config.tick_spacing as i32;
+        require!(tick_lower%tick_spacing==0 && tick_upper%tick_spacing==0,CLMMError::UnalignedTick);
+        let position=&mut ctx.accounts.position;
+        position.pool=ctx.accounts.config.key();
+        position.position_mint=ctx.accounts.position_mint.key();
+        position.tick_lower=tick_lower;
+        position.tick_upper=tick_upper;
+        position.liquidity=0;
+        position.bump=ctx.bumps.position;
+
+        let pool=&ctx.accounts.config;
+        let signer_seed=pool_signer_seeds!(pool);
+        // a Token-2022 position mint gets the pool as its close authority, so
+        // closing the position can reclaim the mint's rent as well
+        let token_2022=ctx.accounts.token_program.key()==spl_token_2022::ID;
+        let extensions:&[ExtensionType]=if token_2022{&[ExtensionType::MintCloseAuthority]}else{&[]};
+        let space=ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions)?;
+        let account=CreateAccount{
+            from:ctx.accounts.signer.to_account_info(),
+            to:ctx.accounts.position_mint.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new(ctx.accounts.system_program.to_account_info(), account);
+        create_account(cpi_ctx, Rent::get()?.minimum_balance(space), space as u64, ctx.accounts.token_program.key)?;
+        if token_2022{
+            let account=MintCloseAuthorityInitialize{
+                token_program_id:ctx.accounts.token_program.to_account_info(),
+                mint:ctx.accounts.position_mint.to_account_info()
+            };
+            let cpi_ctx=CpiContext::new(ctx.accounts.token_program.to_account_info(), account);
+            mint_close_authority_initialize(cpi_ctx, Some(&pool.key()))?;
+        }
+        let account=InitializeMint2{
+            mint:ctx.accounts.position_mint.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new(ctx.accounts.token_program.to_account_info(), account);
+        initialize_mint2(cpi_ctx, 0, &pool.key(), None)?;
+        let account=Create{
+            payer:ctx.accounts.signer.to_account_info(),
+            associated_token:ctx.accounts.position_token_account.to_account_info(),
+            authority:ctx.accounts.signer.to_account_info(),
+            mint:ctx.accounts.position_mint.to_account_info(),
+            system_program:ctx.accounts.system_program.to_account_info(),
+            token_program:ctx.accounts.token_program.to_account_info()
+        };
+        create(CpiContext::new(ctx.accounts.associated_token_program.to_account_info(), account))?;
+        let account=MintTo{
+            mint:ctx.accounts.position_mint.to_account_info(),
+            to:ctx.accounts.position_token_account.to_account_info(),
+            authority:pool.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), account, signer_seed);
+        mint_to(cpi_ctx, 1)?;
+        // drop the mint authority so the position token stays one of one
+        let account=SetAuthority{
+            current_authority:pool.to_account_info(),
+            account_or_mint:ctx.accounts.position_mint.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), account, signer_seed);
+        set_authority(cpi_ctx, AuthorityType::MintTokens, None)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenAccount;
+
+use crate::{error::CLMMError, math::mul_div_floor, state::Pool, utils::{block_timestamp, NUM_REWARDS, Q64, REWARD_FUNDED_SECONDS}};
+
+#[derive(Accounts)]
+pub struct SetRewardEmissions<'info>{
+    pub signer:Signer<'info>,
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
+    pub config:Account<'info,Pool>,
+    pub reward_vault:InterfaceAccount<'info,TokenAccount>,
+}
+impl <'info> SetRewardEmissions<'info>{
+    /// Changes a reward's rate from now on, as long as the vault already holds
+    /// a day's worth at the new rate.
+    pub fn set_reward_emissions(ctx:Context<SetRewardEmissions>,reward_index:u8,emissions_per_second_x64:u128)->Result<()>{
+        let pool=&mut ctx.accounts.config;
+        let index=reward_index as usize;
+        require!(index<NUM_REWARDS && pool.reward_infos[index].initialized(),CLMMError::InvalidRewardIndex);
+        require_keys_eq!(ctx.accounts.signer.key(),pool.reward_infos[index].authority,CLMMError::Unauthorized);
+        require_keys_eq!(ctx.accounts.reward_vault.key(),pool.reward_infos[index].vault,CLMMError::InvalidVault);
+        let emissions_per_day=mul_div_floor(emissions_per_second_x64,REWARD_FUNDED_SECONDS,Q64)?;
+        require!(ctx.accounts.reward_vault.amount as u128>=emissions_per_day,CLMMError::InsufficientRewardVaultBalance);
+        // what was emitted at the old rate is settled first
+        pool.update_rewards(block_timestamp()?)?;
+        pool.reward_infos[index].emissions_per_second_x64=emissions_per_second_x64;
+Ok(())
+    }
+}
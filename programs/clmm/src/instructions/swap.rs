@@ -1,121 +1,381 @@
-use std::iter::Product;
-
-use anchor_lang::{prelude::*, solana_program::system_instruction::transfer, system_program::Transfer};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::{AccountMeta, Instruction}, program::invoke},
+};
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{Mint, TokenAccount, TokenInterface,TransferChecked,transfer_checked,MintTo,mint_to},
+    token_interface::{Mint, TokenAccount, TokenInterface,TransferChecked,transfer_checked},
 };
 
-use crate::{accounts, error::CLMMError, state::{tick, Pool}, utils::{calculate_liquidity_amounts, compute_swap_step, integer_sqrt, tick_to_sqrt_price_x64, TICK_SPACING}};
+use crate::{error::CLMMError, events::Swapped, mint_extensions::{transfer_fee, transfer_inverse_fee}, native_sol::{is_native_mint, open_wsol, unwrap_sol}, state::{pool_signer_seeds, ObservationState, Pool, TickArray, TickArrayBitmapExtension}, utils::{block_timestamp, check_deadline, compute_swap_step, first_initialized_tick_array, next_initialized_tick_array, sqrt_price_x64_to_tick, tick_to_sqrt_price_x64, FEE_RATE_DENOMINATOR, MAX_SQRT_PRICE_X64, MIN_SQRT_PRICE_X64, Q64}};
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Swap<'info>{
 #[account(mut)]
     pub useraccount:Signer<'info>,
 
      pub  pooladmint:SystemAccount<'info>,
+    #[account(mint::token_program=token_program_a)]
     pub minta:InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program=token_program_b)]
     pub mintb:InterfaceAccount<'info, Mint>,
-    #[account(init_if_needed,associated_token::mint=minta,associated_token::authority=useraccount,payer=useraccount)]
-    pub usertoken_account_a:InterfaceAccount<'info,TokenAccount>,
-    #[account(init_if_needed,associated_token::mint=mintb,associated_token::authority=useraccount,payer=useraccount)]
-    pub usertoken_account_b:InterfaceAccount<'info,TokenAccount>,
-    #[account(mut,associated_token::mint=minta,associated_token::authority=config)]
+    /// may be left out for a side paid in native SOL
+    #[account(init_if_needed,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=useraccount,payer=useraccount)]
+    pub usertoken_account_a:Option<InterfaceAccount<'info,TokenAccount>>,
+    #[account(init_if_needed,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=useraccount,payer=useraccount)]
+    pub usertoken_account_b:Option<InterfaceAccount<'info,TokenAccount>>,
+    /// CHECK: the user's ephemeral wSOL account at `[b"wsol", useraccount]`, only used
+    /// with `native_sol` and checked against that address when it is opened
+    #[account(mut)]
+    pub wsol_account:Option<UncheckedAccount<'info>>,
+    #[account(mut,associated_token::mint=minta,associated_token::token_program=token_program_a,associated_token::authority=config)]
     pub vaulta:InterfaceAccount<'info, TokenAccount>,
-    #[account(mut,seeds=[b"config",config.seed.to_le_bytes().as_ref()],bump)]
+    #[account(mut,seeds=[b"pool",config.amm_config.as_ref(),config.minta.as_ref(),config.mintb.as_ref(),config.fee_rate.to_le_bytes().as_ref()],bump=config.bump,constraint=!config.locked @ CLMMError::PoolLocked)]
     pub config:Account<'info,Pool>,
-    #[account(mut,associated_token::mint=mintb,associated_token::authority=config)]
+    #[account(mut,associated_token::mint=mintb,associated_token::token_program=token_program_b,associated_token::authority=config)]
     pub  vault_b:InterfaceAccount<'info, TokenAccount>,
-    #[account(mut,seeds=[b"tick",config.key().as_ref()],bump)]
-    pub uppertick:Account<'info,tick>,
-    #[account(mut,seeds=[b"tick",config.key().as_ref()],bump)]
-    pub lowertick:Account<'info,tick>,
+    #[account(mut,seeds=[b"observation",config.key().as_ref()],bump)]
+    pub observation:AccountLoader<'info,ObservationState>,
+    #[account(seeds=[b"tick_array_bitmap",config.key().as_ref()],bump)]
+    pub tick_array_bitmap:AccountLoader<'info,TickArrayBitmapExtension>,
     pub system_program:Program<'info,System>,
 
-    pub token_program:Interface<'info, TokenInterface>,
+    pub token_program_a:Interface<'info, TokenInterface>,
+    pub token_program_b:Interface<'info, TokenInterface>,
     pub associated_token_program:Program<'info,AssociatedToken>
 }
 impl <'info> Swap<'info> {
-    pub fn swap(ctx:Context<Swap>,amount_in:u64,sqrt_price:Option<u128>,min_amount:Option<u128>,a_to_b:bool)->Result<()>{
-        require!(amount_in>0,CLMMError::ZeroAmount);
-        require!(!ctx.remaining_accounts.is_empty(),CLMMError::MissingTickAccounts);
-        let  pool=&mut ctx.accounts.config;
-        let mut liquidity=pool.active_liqiudity;
-        require!(pool.minta==ctx.accounts.minta.key(),CLMMError::InvalidTokenMint);
-        require!(pool.mintb==ctx.accounts.mintb.key(),CLMMError::InvalidTokenMint);
-        require!(pool.active_liqiudity>0,CLMMError::InsufficientFundsInPool);
-        //since the tick accounts are variable that is why passing in remainig account
-        let mut ticks=vec![];
-        for account in ctx.remaining_accounts.iter(){
-            let data=account.data.borrow_mut();
-            let mut tick_data=&data[8..];
-            let tick:tick=tick::try_deserialize(&mut tick_data)?;
-            ticks.push((account.clone(),tick));
-        }
-    let mut sqrt_price=sqrt_price.unwrap_or_else(|| if  a_to_b{1} else {
-        u128::MAX
-    });
-    let mut remainig_amount=pool.active_liqiudity;
-    let mut total_amount_in:u128=0;
-    let mut total_amount_out:u128=0;
-
-     for (tick_info,tick) in ticks{
-        let next_sqrt=tick_to_sqrt_price_x64(tick.index)?;
-        if (a_to_b && next_sqrt < sqrt_price)
-        || (!a_to_b && next_sqrt > sqrt_price)
-    {
-        break;
-    }
- 
-    let (sqrt_new,computed_amount_in,computed_amount_out)=compute_swap_step(
-        sqrt_price, next_sqrt, pool.active_liqiudity,amount_in as u128, a_to_b)?;
-
-        sqrt_price=sqrt_new;
-        remainig_amount=remainig_amount.checked_sub(computed_amount_in).ok_or(CLMMError::ArithmeticOverflow)?;
-        total_amount_in=total_amount_in.checked_add(computed_amount_in).ok_or(CLMMError::ArithmeticOverflow)?;
-        total_amount_out=total_amount_out.checked_add(computed_amount_out).ok_or(CLMMError::ArithmeticOverflow)?;
-        if sqrt_price==sqrt_new{
-            pool.current_tick=tick.index;
-            if a_to_b{
-                liquidity=liquidity.checked_sub(tick.liquidity as u128).ok_or(CLMMError::ArithmeticOverflow)?;
-            }else{
-                liquidity=liquidity.checked_add(tick.liquidity as u128).ok_or(CLMMError::ArithmeticOverflow)?;
-            }
-
-        }
-
-}     
-   
-   let total_amount_in:u64=total_amount_in.try_into().map_err(|_|CLMMError::AmountTooLarge)?;
-   let total_amount_out:u64=total_amount_out.try_into().map_err(|_|CLMMError::AmountTooLarge)?;
-    if let Some(min_out)=min_amount{
-        require!(total_amount_out>=min_out as u64 ,CLMMError::SlippageExceeded);
+    /// Swaps exactly `amount` of the input token, or for exactly `amount` of
+    /// the output token when `amount_specified_is_input` is false. With
+    /// `native_sol` the native side is paid in lamports, or paid out as them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn swap(ctx:Context<'_,'_,'info,'info,Swap<'info>>,amount:u64,sqrt_price_limit:Option<u128>,min_amount_out:Option<u64>,max_amount_in:Option<u64>,amount_specified_is_input:bool,a_to_b:bool,native_sol:bool,deadline:Option<i64>)->Result<()>{
+        check_deadline(deadline)?;
+        let (native_a,native_b)=(native_sol && is_native_mint(&ctx.accounts.minta),native_sol && is_native_mint(&ctx.accounts.mintb));
+        require!(!native_sol || native_a || native_b,CLMMError::NativeMintNotInPool);
+        let SwapResult{amount_in:total_amount_in,amount_out:total_amount_out,fee_amount,protocol_fee,sqrt_price_before,tick_before}=execute_swap(&mut ctx.accounts.config,&ctx.accounts.minta,&ctx.accounts.mintb,&ctx.accounts.observation,&ctx.accounts.tick_array_bitmap,ctx.remaining_accounts,amount,sqrt_price_limit,amount_specified_is_input,a_to_b)?;
+        let (mint_in,mint_out)=if a_to_b{(&ctx.accounts.minta,&ctx.accounts.mintb)}else{(&ctx.accounts.mintb,&ctx.accounts.minta)};
+   // slippage bounds are on what the user actually pays and receives
+   let user_amount_in=total_amount_in.checked_add(transfer_inverse_fee(mint_in,total_amount_in)?).ok_or(CLMMError::ArithmeticOverflow)?;
+   let user_amount_out=total_amount_out-transfer_fee(mint_out,total_amount_out)?;
+    if let Some(min_out)=min_amount_out{
+        require!(user_amount_out>=min_out,CLMMError::SlippageExceeded);
     }  
-  require!(total_amount_out>0,CLMMError::ZeroSwapOutput);
-  pool.sqrt_price=sqrt_price;
-  pool.active_liqiudity=liquidity;
-  let seed=pool.seed.to_be_bytes();
-let seeds:&[&[u8]]=&[b"config",seed.as_ref(),&[pool.bump]];
-let signer_seed=&[seeds];
+    if let Some(max_in)=max_amount_in{
+        require!(user_amount_in<=max_in,CLMMError::SlippageExceeded);
+    }
+  require!(user_amount_out>0,CLMMError::ZeroSwapOutput);
+  let pool=&ctx.accounts.config;
+  let signer_seed=pool_signer_seeds!(pool);
+// the native side pays or is paid through an ephemeral wSOL account, funded with what it pays
+let user_account_a=if native_a{
+    open_wsol(&ctx.accounts.useraccount,&ctx.accounts.wsol_account,&ctx.accounts.minta,if a_to_b{user_amount_in}else{0},&ctx.accounts.system_program,&ctx.accounts.token_program_a)?
+}else{
+    ctx.accounts.usertoken_account_a.as_ref().ok_or(CLMMError::MissingTokenAccount)?.to_account_info()
+};
+let user_account_b=if native_b{
+    open_wsol(&ctx.accounts.useraccount,&ctx.accounts.wsol_account,&ctx.accounts.mintb,if a_to_b{0}else{user_amount_in},&ctx.accounts.system_program,&ctx.accounts.token_program_b)?
+}else{
+    ctx.accounts.usertoken_account_b.as_ref().ok_or(CLMMError::MissingTokenAccount)?.to_account_info()
+};
 if a_to_b{
     let account=TransferChecked{
-        from:ctx.accounts.usertoken_account_a.to_account_info(),
+        from:user_account_a.clone(),
         to:ctx.accounts.vaulta.to_account_info(),
         authority:ctx.accounts.useraccount.to_account_info(),
         mint:ctx.accounts.minta.to_account_info()
     };
-    let cpi_ctx=CpiContext::new(ctx.accounts.token_program.to_account_info(), account);
-    transfer_checked(cpi_ctx, total_amount_in, ctx.accounts.minta.decimals)?;
+    let cpi_ctx=CpiContext::new(ctx.accounts.token_program_a.to_account_info(), account);
+    transfer_checked(cpi_ctx, user_amount_in, ctx.accounts.minta.decimals)?;
     let account=TransferChecked{
-        to:ctx.accounts.usertoken_account_b.to_account_info(),
+        to:user_account_b.clone(),
         from:ctx.accounts.vault_b.to_account_info(),
-        authority:ctx.accounts.config.to_account_info(),
+        authority:pool.to_account_info(),
         mint:ctx.accounts.mintb.to_account_info()
     };
-    let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), account,signer_seed);
+    let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_b.to_account_info(), account,signer_seed);
     transfer_checked(cpi_ctx, total_amount_out, ctx.accounts.mintb.decimals)?;
+}else{
+    let account=TransferChecked{
+        from:user_account_b.clone(),
+        to:ctx.accounts.vault_b.to_account_info(),
+        authority:ctx.accounts.useraccount.to_account_info(),
+        mint:ctx.accounts.mintb.to_account_info()
+    };
+    let cpi_ctx=CpiContext::new(ctx.accounts.token_program_b.to_account_info(), account);
+    transfer_checked(cpi_ctx, user_amount_in, ctx.accounts.mintb.decimals)?;
+    let account=TransferChecked{
+        to:user_account_a.clone(),
+        from:ctx.accounts.vaulta.to_account_info(),
+        authority:pool.to_account_info(),
+        mint:ctx.accounts.minta.to_account_info()
+    };
+    let cpi_ctx=CpiContext::new_with_signer(ctx.accounts.token_program_a.to_account_info(), account,signer_seed);
+    transfer_checked(cpi_ctx, total_amount_out, ctx.accounts.minta.decimals)?;
 }
+if native_a{
+    unwrap_sol(&ctx.accounts.useraccount,&user_account_a,&ctx.accounts.token_program_a)?;
+}
+if native_b{
+    unwrap_sol(&ctx.accounts.useraccount,&user_account_b,&ctx.accounts.token_program_b)?;
+}
+emit_cpi!(Swapped{
+    pool:ctx.accounts.config.key(),
+    sender:ctx.accounts.useraccount.key(),
+    a_to_b,
+    amount_in:total_amount_in,
+    amount_out:total_amount_out,
+    fee_amount,
+    protocol_fee,
+    sqrt_price_before,
+    sqrt_price_after:ctx.accounts.config.sqrt_price,
+    tick_before,
+    tick_after:ctx.accounts.config.current_tick,
+    liquidity_after:ctx.accounts.config.active_liqiudity,
+});
+        Ok(())
+
+    }
+
+    /// Swaps like `swap`, but sends the output first and then calls the program
+    /// at `remaining_accounts[tick_array_count]` with the rest of the remaining
+    /// accounts and `callback_data`, followed by the input amount owed and the
+    /// output amount received as little-endian u64s. The input vault must have
+    /// grown by what the swap needs once the callback returns. The pool stays
+    /// locked during the callback, so it cannot re-enter the pool.
+    #[allow(clippy::too_many_arguments)]
+    pub fn flash_swap(ctx:Context<'_,'_,'info,'info,Swap<'info>>,amount:u64,sqrt_price_limit:Option<u128>,amount_specified_is_input:bool,a_to_b:bool,tick_array_count:u8,callback_data:Vec<u8>,deadline:Option<i64>)->Result<()>{
+        check_deadline(deadline)?;
+        let tick_array_count=tick_array_count as usize;
+        require!(ctx.remaining_accounts.len()>tick_array_count,CLMMError::MissingCallbackProgram);
+        let (tick_array_accounts,callback_accounts)=ctx.remaining_accounts.split_at(tick_array_count);
+        let SwapResult{amount_in,amount_out,fee_amount,protocol_fee,sqrt_price_before,tick_before}=execute_swap(&mut ctx.accounts.config,&ctx.accounts.minta,&ctx.accounts.mintb,&ctx.accounts.observation,&ctx.accounts.tick_array_bitmap,tick_array_accounts,amount,sqrt_price_limit,amount_specified_is_input,a_to_b)?;
+        require!(amount_out>0,CLMMError::ZeroSwapOutput);
+        let (mint_in,mint_out)=if a_to_b{(&ctx.accounts.minta,&ctx.accounts.mintb)}else{(&ctx.accounts.mintb,&ctx.accounts.minta)};
+        let amount_owed=amount_in.checked_add(transfer_inverse_fee(mint_in,amount_in)?).ok_or(CLMMError::ArithmeticOverflow)?;
+        let amount_received=amount_out-transfer_fee(mint_out,amount_out)?;
+
+        let pool=&ctx.accounts.config;
+        let signer_seed=pool_signer_seeds!(pool);
+        let (vault_out,user_out,mint_out,token_program_out)=if a_to_b{
+            (&ctx.accounts.vault_b,&ctx.accounts.usertoken_account_b,&ctx.accounts.mintb,&ctx.accounts.token_program_b)
+        }else{
+            (&ctx.accounts.vaulta,&ctx.accounts.usertoken_account_a,&ctx.accounts.minta,&ctx.accounts.token_program_a)
+        };
+        let account=TransferChecked{
+            from:vault_out.to_account_info(),
+            to:user_out.as_ref().ok_or(CLMMError::MissingTokenAccount)?.to_account_info(),
+            authority:pool.to_account_info(),
+            mint:mint_out.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new_with_signer(token_program_out.to_account_info(), account, signer_seed);
+        transfer_checked(cpi_ctx, amount_out, mint_out.decimals)?;
+
+        // the callback sees the pool as it is after the swap, and locked
+        ctx.accounts.config.locked=true;
+        ctx.accounts.config.exit(&crate::ID)?;
+        let balance_before=if a_to_b{ctx.accounts.vaulta.amount}else{ctx.accounts.vault_b.amount};
+        let callback_program=&callback_accounts[0];
+        let mut data=callback_data;
+        data.extend_from_slice(&amount_owed.to_le_bytes());
+        data.extend_from_slice(&amount_received.to_le_bytes());
+        let instruction=Instruction{
+            program_id:callback_program.key(),
+            accounts:callback_accounts[1..].iter().map(|account|AccountMeta{pubkey:account.key(),is_signer:account.is_signer,is_writable:account.is_writable}).collect(),
+            data,
+        };
+        invoke(&instruction,callback_accounts)?;
+
+        let vault_in=if a_to_b{&mut ctx.accounts.vaulta}else{&mut ctx.accounts.vault_b};
+        vault_in.reload()?;
+        let paid=vault_in.amount.saturating_sub(balance_before);
+        require!(paid>=amount_in,CLMMError::FlashSwapNotPaid);
+        let pool=&mut ctx.accounts.config;
+        pool.locked=false;
+        emit_cpi!(Swapped{
+            pool:pool.key(),
+            sender:ctx.accounts.useraccount.key(),
+            a_to_b,
+            amount_in,
+            amount_out,
+            fee_amount,
+            protocol_fee,
+            sqrt_price_before,
+            sqrt_price_after:pool.sqrt_price,
+            tick_before,
+            tick_after:pool.current_tick,
+            liquidity_after:pool.active_liqiudity,
+        });
         Ok(())
+    }
+}
+
+/// What a swap moved through the vaults and the state it started from.
+pub(crate) struct SwapResult{
+    pub amount_in:u64,
+    pub amount_out:u64,
+    pub fee_amount:u64,
+    pub protocol_fee:u64,
+    pub sqrt_price_before:u128,
+    pub tick_before:i32,
+}
+
+/// Runs a swap against the pool's liquidity and writes the new pool state,
+/// leaving the token transfers to the caller.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn execute_swap<'info>(pool:&mut Account<'info,Pool>,minta:&InterfaceAccount<'info,Mint>,mintb:&InterfaceAccount<'info,Mint>,observation:&AccountLoader<'info,ObservationState>,tick_array_bitmap:&AccountLoader<'info,TickArrayBitmapExtension>,tick_array_accounts:&'info [AccountInfo<'info>],amount:u64,sqrt_price_limit:Option<u128>,amount_specified_is_input:bool,a_to_b:bool)->Result<SwapResult>{
+    require!(amount>0,CLMMError::ZeroAmount);
+    let mut liquidity=pool.active_liqiudity;
+    require!(pool.minta==minta.key(),CLMMError::InvalidTokenMint);
+    require!(pool.mintb==mintb.key(),CLMMError::InvalidTokenMint);
+    // record the price and liquidity that held up to this swap, and pay out
+    // rewards to that liquidity before the swap moves the price
+    let now=block_timestamp()?;
+    observation.load_mut()?.write(now,pool.current_tick,pool.active_liqiudity);
+    pool.update_rewards(now)?;
+    let reward_growths_global=pool.reward_growths_global();
+    //tick arrays are passed in remaining accounts in swap direction, starting from
+    //the first array with initialized ticks, and each must be the next one set in the pool bitmap
+    let mut ticks=vec![];
+    let mut tick_arrays=vec![];
+    let tick_spacing=pool.tick_spacing as i32;
+    let bitmap_extension=tick_array_bitmap.load()?;
+    let mut expected_start=first_initialized_tick_array(&pool.tick_array_bitmap,&bitmap_extension,pool.current_tick,tick_spacing,a_to_b);
+    let mut search_tick=pool.current_tick;
+    for account in tick_array_accounts.iter(){
+        let Some(start)=expected_start else{
+            break;
+        };
+        let loader=AccountLoader::<TickArray>::try_from(account)?;
+        {
+            let tick_array=loader.load()?;
+            require_keys_eq!(tick_array.pool,pool.key(),CLMMError::InvalidTickArray);
+            require!(tick_array.start_tick_index==start,CLMMError::InvalidTickArray);
+            while let Some(tick)=tick_array.next_initialized_tick(search_tick,a_to_b){
+                ticks.push((tick_arrays.len(),tick.tick));
+                search_tick=if a_to_b{tick.tick-1}else{tick.tick};
+            }
+        }
+        tick_arrays.push(loader);
+        expected_start=next_initialized_tick_array(&pool.tick_array_bitmap,&bitmap_extension,start,tick_spacing,a_to_b);
+    }
+    let sqrt_price_limit=sqrt_price_limit.unwrap_or(if a_to_b{MIN_SQRT_PRICE_X64}else{MAX_SQRT_PRICE_X64-1});
+    if a_to_b{
+        require!(sqrt_price_limit<pool.sqrt_price && sqrt_price_limit>=MIN_SQRT_PRICE_X64,CLMMError::InvalidSqrtPriceLimit);
+    }else{
+        require!(sqrt_price_limit>pool.sqrt_price && sqrt_price_limit<MAX_SQRT_PRICE_X64,CLMMError::InvalidSqrtPriceLimit);
+    }
+    let mut sqrt_price=pool.sqrt_price;
+    let mut current_tick=pool.current_tick;
+    // the curve only sees what lands in or leaves the vaults, so transfer
+    // fees come off an exact input and go on top of an exact output
+    let (mint_in,mint_out)=if a_to_b{(&minta,&mintb)}else{(&mintb,&minta)};
+    let amount_specified=if amount_specified_is_input{
+        amount-transfer_fee(mint_in,amount)?
+    }else{
+        amount.checked_add(transfer_inverse_fee(mint_out,amount)?).ok_or(CLMMError::ArithmeticOverflow)?
+    };
+    require!(amount_specified>0,CLMMError::ZeroAmount);
+    let mut amount_remaining=amount_specified as u128;
+    let mut total_amount_in:u128=0;
+    let mut total_amount_out:u128=0;
+    let mut fee_growth_global_a=pool.fee_growth_global_a;
+    let mut fee_growth_global_b=pool.fee_growth_global_b;
+    let mut total_fee:u128=0;
+    let mut protocol_fee:u128=0;
+    let mut ticks=ticks.into_iter().peekable();
+
+    // each step moves the price to the next initialized tick or the limit,
+    // whichever comes first, until the amount is used up or the limit is hit
+    while amount_remaining>0 && sqrt_price!=sqrt_price_limit{
+        let next_tick=ticks.peek().copied();
+        let next_tick_sqrt_price=next_tick.map(|(_,tick_index)|tick_to_sqrt_price_x64(tick_index)).transpose()?;
+        let sqrt_price_target=match next_tick_sqrt_price{
+            Some(next_sqrt)=>{
+                if a_to_b{next_sqrt.max(sqrt_price_limit)}else{next_sqrt.min(sqrt_price_limit)}
+            }
+            None=>{
+                // no tick arrays were passed for the initialized ticks beyond this point
+                require!(expected_start.is_none(),CLMMError::MissingTickAccounts);
+                sqrt_price_limit
+            }
+        };
+        let (sqrt_price_next,step_amount_in,step_amount_out,fee_amount)=if liquidity==0{
+            (sqrt_price_target,0,0,0)
+        }else{
+            compute_swap_step(sqrt_price,sqrt_price_target,liquidity,amount_remaining,pool.fee_rate,amount_specified_is_input,a_to_b)?
+        };
+        let step_amount_in=step_amount_in.checked_add(fee_amount).ok_or(CLMMError::ArithmeticOverflow)?;
+        let step_amount=if amount_specified_is_input{step_amount_in}else{step_amount_out};
+        amount_remaining=amount_remaining.checked_sub(step_amount).ok_or(CLMMError::ArithmeticOverflow)?;
+        total_amount_in=total_amount_in.checked_add(step_amount_in).ok_or(CLMMError::ArithmeticOverflow)?;
+        total_amount_out=total_amount_out.checked_add(step_amount_out).ok_or(CLMMError::ArithmeticOverflow)?;
+
+        // the protocol takes its cut first, the rest of the fee is shared by the liquidity in range
+        let step_protocol_fee=fee_amount.checked_mul(pool.protocol_fee_rate as u128).ok_or(CLMMError::ArithmeticOverflow)?/FEE_RATE_DENOMINATOR as u128;
+        total_fee=total_fee.checked_add(fee_amount).ok_or(CLMMError::ArithmeticOverflow)?;
+        protocol_fee=protocol_fee.checked_add(step_protocol_fee).ok_or(CLMMError::ArithmeticOverflow)?;
+        let fee_amount=fee_amount-step_protocol_fee;
+        if let Some(fee_growth)=fee_amount.checked_mul(Q64).ok_or(CLMMError::ArithmeticOverflow)?.checked_div(liquidity){
+            if a_to_b{
+                fee_growth_global_a=fee_growth_global_a.wrapping_add(fee_growth);
+            }else{
+                fee_growth_global_b=fee_growth_global_b.wrapping_add(fee_growth);
+            }
+        }
 
+        match next_tick{
+            Some((array_index,tick_index)) if Some(sqrt_price_next)==next_tick_sqrt_price=>{
+                ticks.next();
+                let liquidity_net={
+                    let mut tick_array=tick_arrays[array_index].load_mut()?;
+                    tick_array.get_tick_mut(tick_index,tick_spacing)?.cross(fee_growth_global_a,fee_growth_global_b,&reward_growths_global)
+                };
+                // liquidity_net is what crossing upwards adds, moving down takes it away
+                let liquidity_net=if a_to_b{-liquidity_net}else{liquidity_net};
+                liquidity=if liquidity_net>=0{
+                    liquidity.checked_add(liquidity_net as u128)
+                }else{
+                    liquidity.checked_sub(liquidity_net.unsigned_abs())
+                }.ok_or(CLMMError::ArithmeticOverflow)?;
+                current_tick=if a_to_b{tick_index-1}else{tick_index};
+            }
+            _=>{
+                if sqrt_price_next!=sqrt_price{
+                    current_tick=sqrt_price_x64_to_tick(sqrt_price_next)?;
+                }
+            }
+        }
+        let reached_target=sqrt_price_next==sqrt_price_target;
+        sqrt_price=sqrt_price_next;
+        if !reached_target{
+            // a step short of its target used up everything that was left
+            break;
+        }
     }
-}
\ No newline at end of file
+
+    let total_amount_in:u64=total_amount_in.try_into().map_err(|_|CLMMError::AmountTooLarge)?;
+    let total_amount_out:u64=total_amount_out.try_into().map_err(|_|CLMMError::AmountTooLarge)?;
+    let (sqrt_price_before,tick_before)=(pool.sqrt_price,pool.current_tick);
+    pool.sqrt_price=sqrt_price;
+    pool.current_tick=current_tick;
+    pool.active_liqiudity=liquidity;
+    pool.fee_growth_global_a=fee_growth_global_a;
+    pool.fee_growth_global_b=fee_growth_global_b;
+    let protocol_fee:u64=protocol_fee.try_into().map_err(|_|CLMMError::AmountTooLarge)?;
+    if a_to_b{
+        pool.protocol_fees_owed_a=pool.protocol_fees_owed_a.checked_add(protocol_fee).ok_or(CLMMError::ArithmeticOverflow)?;
+    }else{
+        pool.protocol_fees_owed_b=pool.protocol_fees_owed_b.checked_add(protocol_fee).ok_or(CLMMError::ArithmeticOverflow)?;
+    }
+    Ok(SwapResult{
+        amount_in:total_amount_in,
+        amount_out:total_amount_out,
+        fee_amount:total_fee.try_into().map_err(|_|CLMMError::AmountTooLarge)?,
+        protocol_fee,
+        sqrt_price_before,
+        tick_before,
+    })
+}
+
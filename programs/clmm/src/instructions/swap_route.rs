@@ -0,0 +1,182 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{error::CLMMError, events::Swapped, instructions::swap::{execute_swap, SwapResult}, mint_extensions::{transfer_fee, transfer_inverse_fee}, state::{pool_signer_seeds, ObservationState, Pool, TickArrayBitmapExtension}, utils::check_deadline};
+
+/// accounts each hop passes in `remaining_accounts` ahead of its tick arrays
+const HOP_ACCOUNTS:usize=9;
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SwapRoute<'info>{
+    #[account(mut)]
+    pub useraccount:Signer<'info>,
+    #[account(mint::token_program=token_program_in)]
+    pub mint_in:InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program=token_program_out)]
+    pub mint_out:InterfaceAccount<'info, Mint>,
+    #[account(mut,associated_token::mint=mint_in,associated_token::token_program=token_program_in,associated_token::authority=useraccount)]
+    pub usertoken_account_in:InterfaceAccount<'info,TokenAccount>,
+    #[account(init_if_needed,associated_token::mint=mint_out,associated_token::token_program=token_program_out,associated_token::authority=useraccount,payer=useraccount)]
+    pub usertoken_account_out:InterfaceAccount<'info,TokenAccount>,
+    pub system_program:Program<'info,System>,
+    pub token_program_in:Interface<'info, TokenInterface>,
+    pub token_program_out:Interface<'info, TokenInterface>,
+    pub associated_token_program:Program<'info,AssociatedToken>
+}
+
+/// One pool along a route, checked the way `Swap` checks its accounts
+struct RouteHop<'info>{
+    pool:Account<'info,Pool>,
+    observation:AccountLoader<'info,ObservationState>,
+    tick_array_bitmap:AccountLoader<'info,TickArrayBitmapExtension>,
+    vaulta:InterfaceAccount<'info, TokenAccount>,
+    vault_b:InterfaceAccount<'info, TokenAccount>,
+    minta:InterfaceAccount<'info, Mint>,
+    mintb:InterfaceAccount<'info, Mint>,
+    token_program_a:Interface<'info, TokenInterface>,
+    token_program_b:Interface<'info, TokenInterface>,
+    tick_arrays:&'info [AccountInfo<'info>],
+}
+
+impl <'info> RouteHop<'info>{
+    fn load(accounts:&'info [AccountInfo<'info>])->Result<Self>{
+        let pool=Account::<Pool>::try_from(&accounts[0])?;
+        require!(!pool.locked,CLMMError::PoolLocked);
+        let observation=AccountLoader::<ObservationState>::try_from(&accounts[1])?;
+        require_keys_eq!(observation.load()?.pool,pool.key(),CLMMError::InvalidRoute);
+        let tick_array_bitmap=AccountLoader::<TickArrayBitmapExtension>::try_from(&accounts[2])?;
+        require_keys_eq!(tick_array_bitmap.load()?.pool,pool.key(),CLMMError::InvalidRoute);
+        let minta=InterfaceAccount::<Mint>::try_from(&accounts[5])?;
+        let mintb=InterfaceAccount::<Mint>::try_from(&accounts[6])?;
+        require_keys_eq!(minta.key(),pool.minta,CLMMError::InvalidTokenMint);
+        require_keys_eq!(mintb.key(),pool.mintb,CLMMError::InvalidTokenMint);
+        let token_program_a=Interface::<TokenInterface>::try_from(&accounts[7])?;
+        let token_program_b=Interface::<TokenInterface>::try_from(&accounts[8])?;
+        require_keys_eq!(*minta.to_account_info().owner,token_program_a.key(),CLMMError::InvalidTokenMint);
+        require_keys_eq!(*mintb.to_account_info().owner,token_program_b.key(),CLMMError::InvalidTokenMint);
+        let vaulta=InterfaceAccount::<TokenAccount>::try_from(&accounts[3])?;
+        let vault_b=InterfaceAccount::<TokenAccount>::try_from(&accounts[4])?;
+        require_keys_eq!(vaulta.key(),get_associated_token_address_with_program_id(&pool.key(),&minta.key(),&token_program_a.key()),CLMMError::InvalidVault);
+        require_keys_eq!(vault_b.key(),get_associated_token_address_with_program_id(&pool.key(),&mintb.key(),&token_program_b.key()),CLMMError::InvalidVault);
+        Ok(RouteHop{pool,observation,tick_array_bitmap,vaulta,vault_b,minta,mintb,token_program_a,token_program_b,tick_arrays:&accounts[HOP_ACCOUNTS..]})
+    }
+
+    /// the vault, mint and token program on one side of the pool
+    fn side(&self,a:bool)->(&InterfaceAccount<'info, TokenAccount>,&InterfaceAccount<'info, Mint>,&Interface<'info, TokenInterface>){
+        if a{(&self.vaulta,&self.minta,&self.token_program_a)}else{(&self.vault_b,&self.mintb,&self.token_program_b)}
+    }
+
+    /// which side of this pool holds `mint`
+    fn holds_a(&self,mint:Pubkey)->Result<bool>{
+        if mint==self.pool.minta{
+            Ok(true)
+        }else{
+            require_keys_eq!(mint,self.pool.mintb,CLMMError::InvalidRoute);
+            Ok(false)
+        }
+    }
+
+    fn pay_out(&self,to:AccountInfo<'info>,amount:u64,a_to_b:bool)->Result<()>{
+        let pool=&self.pool;
+        let signer_seed=pool_signer_seeds!(pool);
+        let (vault,mint,token_program)=self.side(!a_to_b);
+        let account=TransferChecked{
+            from:vault.to_account_info(),
+            to,
+            authority:pool.to_account_info(),
+            mint:mint.to_account_info()
+        };
+        let cpi_ctx=CpiContext::new_with_signer(token_program.to_account_info(), account, signer_seed);
+        transfer_checked(cpi_ctx, amount, mint.decimals)
+    }
+}
+
+impl <'info> SwapRoute<'info>{
+    /// Swaps exactly `amount_in` of `mint_in` into `mint_out` through a route of
+    /// pools, in order. Each hop passes its pool, observation, tick array bitmap
+    /// extension, vault A, vault B, mint A, mint B and the two token programs in
+    /// `remaining_accounts`, followed by `tick_array_counts[i]` tick arrays as
+    /// `swap` takes them.
+    /// A hop's output goes straight into the next pool's vault, and only the
+    /// final output is held to `min_amount_out`.
+    pub fn swap_route(ctx:Context<'_,'_,'info,'info,SwapRoute<'info>>,amount_in:u64,min_amount_out:u64,tick_array_counts:Vec<u8>,deadline:Option<i64>)->Result<()>{
+        check_deadline(deadline)?;
+        require!(!tick_array_counts.is_empty(),CLMMError::InvalidRoute);
+        let mut hops:Vec<RouteHop>=Vec::with_capacity(tick_array_counts.len());
+        let mut remaining=ctx.remaining_accounts;
+        for count in tick_array_counts{
+            let hop_len=HOP_ACCOUNTS+count as usize;
+            require!(remaining.len()>=hop_len,CLMMError::InvalidRoute);
+            let (accounts,rest)=remaining.split_at(hop_len);
+            let hop=RouteHop::load(accounts)?;
+            // a pool loaded twice would have one copy's state overwrite the other's
+            require!(hops.iter().all(|other|other.pool.key()!=hop.pool.key()),CLMMError::InvalidRoute);
+            hops.push(hop);
+            remaining=rest;
+        }
+        require!(remaining.is_empty(),CLMMError::InvalidRoute);
+
+        let last=hops.len()-1;
+        let mut mint=ctx.accounts.mint_in.key();
+        let mut amount=amount_in;
+        for index in 0..hops.len(){
+            let a_to_b=hops[index].holds_a(mint)?;
+            let hop=&mut hops[index];
+            let SwapResult{amount_in:hop_amount_in,amount_out,fee_amount,protocol_fee,sqrt_price_before,tick_before}=execute_swap(&mut hop.pool,&hop.minta,&hop.mintb,&hop.observation,&hop.tick_array_bitmap,hop.tick_arrays,amount,None,true,a_to_b)?;
+            let hop=&hops[index];
+            let (vault_in,mint_in,_)=hop.side(a_to_b);
+            let (_,mint_out,_)=hop.side(!a_to_b);
+            if index==0{
+                // the user pays only what the first pool takes, plus its transfer fee
+                let user_amount_in=hop_amount_in.checked_add(transfer_inverse_fee(mint_in,hop_amount_in)?).ok_or(CLMMError::ArithmeticOverflow)?;
+                let account=TransferChecked{
+                    from:ctx.accounts.usertoken_account_in.to_account_info(),
+                    to:vault_in.to_account_info(),
+                    authority:ctx.accounts.useraccount.to_account_info(),
+                    mint:ctx.accounts.mint_in.to_account_info()
+                };
+                let cpi_ctx=CpiContext::new(ctx.accounts.token_program_in.to_account_info(), account);
+                transfer_checked(cpi_ctx, user_amount_in, ctx.accounts.mint_in.decimals)?;
+            }else{
+                // the previous hop already sent its whole output here, none of it may be left over
+                require!(hop_amount_in==amount-transfer_fee(mint_in,amount)?,CLMMError::RouteHopNotFilled);
+            }
+            require!(amount_out>0,CLMMError::ZeroSwapOutput);
+            mint=mint_out.key();
+            let to=if index==last{
+                require_keys_eq!(mint,ctx.accounts.mint_out.key(),CLMMError::InvalidRoute);
+                let user_amount_out=amount_out-transfer_fee(mint_out,amount_out)?;
+                require!(user_amount_out>=min_amount_out,CLMMError::SlippageExceeded);
+                ctx.accounts.usertoken_account_out.to_account_info()
+            }else{
+                let next=&hops[index+1];
+                next.side(next.holds_a(mint)?).0.to_account_info()
+            };
+            hop.pay_out(to,amount_out,a_to_b)?;
+            emit_cpi!(Swapped{
+                pool:hop.pool.key(),
+                sender:ctx.accounts.useraccount.key(),
+                a_to_b,
+                amount_in:hop_amount_in,
+                amount_out,
+                fee_amount,
+                protocol_fee,
+                sqrt_price_before,
+                sqrt_price_after:hop.pool.sqrt_price,
+                tick_before,
+                tick_after:hop.pool.current_tick,
+                liquidity_after:hop.pool.active_liqiudity,
+            });
+            amount=amount_out;
+        }
+        // the pools were loaded by hand, so their new state is written back by hand
+        for hop in hops.iter(){
+            hop.pool.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, state::AmmConfig};
+
+#[derive(Accounts)]
+pub struct TransferAdmin<'info>{
+    #[account(address=amm_config.admin @ CLMMError::Unauthorized)]
+    pub signer:Signer<'info>,
+    #[account(mut,seeds=[b"amm_config"],bump=amm_config.bump)]
+    pub amm_config:Account<'info,AmmConfig>,
+}
+impl <'info> TransferAdmin<'info>{
+    /// Nominates `new_admin`, who only takes over once it signs `accept_admin`
+    pub fn transfer_admin(ctx:Context<TransferAdmin>,new_admin:Pubkey)->Result<()>{
+        ctx.accounts.amm_config.pending_admin=new_admin;
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, state::{AmmConfig, FeeTier}};
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info>{
+    #[account(address=amm_config.admin @ CLMMError::Unauthorized)]
+    pub signer:Signer<'info>,
+    #[account(mut,seeds=[b"amm_config"],bump=amm_config.bump)]
+    pub amm_config:Account<'info,AmmConfig>,
+}
+impl <'info> UpdateConfig<'info>{
+    /// Replaces whichever settings are given. Existing pools keep the fee
+    /// tier they were created with.
+    pub fn update_config(ctx:Context<UpdateConfig>,treasury:Option<Pubkey>,fee_tiers:Option<Vec<FeeTier>>)->Result<()>{
+        let amm_config=&mut ctx.accounts.amm_config;
+        if let Some(treasury)=treasury{
+            amm_config.treasury=treasury;
+        }
+        if let Some(fee_tiers)=fee_tiers{
+            AmmConfig::validate_fee_tiers(&fee_tiers)?;
+            amm_config.fee_tiers=fee_tiers;
+        }
+        Ok(())
+    }
+}
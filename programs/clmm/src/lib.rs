@@ -1,50 +1,167 @@
 use anchor_lang::prelude::*;
 mod state;
+pub use state::{FeeTier, InitialPrice, ObserveResult};
 pub mod instructions;
 pub use instructions::*;
 mod error;
 mod utils;
+mod math;
+mod mint_extensions;
+mod native_sol;
+mod events;
+pub use events::*;
 declare_id!("BqGdgHyFoLxyrcgatBXCXqqUDqK1PUrRNbReAY1cxbp3");
 
 #[program]
 pub mod clmm {
     use super::*;
 
-    pub fn init_pool(ctx: Context<InitializePool>, seed: u64, price: u64) -> Result<()> {
-        InitializePool::initializepool(ctx, price, seed)
+    pub fn create_config(ctx: Context<CreateConfig>, treasury: Pubkey, fee_tiers: Vec<FeeTier>) -> Result<()> {
+        CreateConfig::create_config(ctx, treasury, fee_tiers)
     }
 
-    pub fn init_tick(ctx: Context<InitialTick>) -> Result<()> {
-        InitialTick::initializetick(ctx)
+    pub fn update_config(ctx: Context<UpdateConfig>, treasury: Option<Pubkey>, fee_tiers: Option<Vec<FeeTier>>) -> Result<()> {
+        UpdateConfig::update_config(ctx, treasury, fee_tiers)
     }
 
-    pub fn add_liquidity(
-        ctx: Context<Liquidity>,
-        tick_lower: i32,
-        tick_upper: i32,
+    pub fn transfer_admin(ctx: Context<TransferAdmin>, new_admin: Pubkey) -> Result<()> {
+        TransferAdmin::transfer_admin(ctx, new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        AcceptAdmin::accept_admin(ctx)
+    }
+
+    pub fn init_pool(ctx: Context<InitializePool>, initial_price: InitialPrice, fee_rate: u32) -> Result<()> {
+        InitializePool::initializepool(ctx, initial_price, fee_rate)
+    }
+
+    pub fn init_tick_array(ctx: Context<InitialTickArray>, start_tick_index: i32) -> Result<()> {
+        InitialTickArray::initializetickarray(ctx, start_tick_index)
+    }
+
+    pub fn open_position(ctx: Context<OpenPosition>, tick_lower: i32, tick_upper: i32) -> Result<()> {
+        OpenPosition::open_position(ctx, tick_lower, tick_upper)
+    }
+
+    pub fn increase_liquidity(
+        ctx: Context<IncreaseLiquidity>,
         liquidity: u128,
+        token_max_a: u64,
+        token_max_b: u64,
+        native_sol: bool,
+        deadline: Option<i64>,
     ) -> Result<()> {
-        Liquidity::add_liqiudity(ctx, tick_lower, tick_upper, liquidity as u64)
+        IncreaseLiquidity::increase_liquidity(ctx, liquidity, token_max_a, token_max_b, native_sol, deadline)
     }
 
-    pub fn withdraw_liquidity(
-        ctx: Context<Withdraw>,
-        tick_lower: i32,
-        tick_upper: i32,
-        liquidity_to_remove: u128,
+    pub fn increase_liquidity_by_amounts(
+        ctx: Context<IncreaseLiquidity>,
+        amount_a_max: u64,
+        amount_b_max: u64,
+        base_token_a: Option<bool>,
+        native_sol: bool,
+        deadline: Option<i64>,
     ) -> Result<()> {
-        Withdraw::add_liqiudity(ctx, tick_lower, tick_upper, liquidity_to_remove as u64)
+        IncreaseLiquidity::increase_liquidity_by_amounts(ctx, amount_a_max, amount_b_max, base_token_a, native_sol, deadline)
     }
 
-    pub fn swap(
-        ctx: Context<Swap>,
-        amount_in: u64,
+    pub fn decrease_liquidity(
+        ctx: Context<DecreaseLiquidity>,
+        liquidity: u128,
+        token_min_a: u64,
+        token_min_b: u64,
+        native_sol: bool,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        DecreaseLiquidity::decrease_liquidity(ctx, liquidity, token_min_a, token_min_b, native_sol, deadline)
+    }
+
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        ClosePosition::close_position(ctx)
+    }
+
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        CollectFees::collect_fees(ctx)
+    }
+
+    pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+        CollectProtocolFees::collect_protocol_fees(ctx)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        amount: u64,
         sqrt_price_limit: Option<u128>,
-        min_amount_out: Option<u128>,
+        min_amount_out: Option<u64>,
+        max_amount_in: Option<u64>,
+        amount_specified_is_input: bool,
         a_to_b: bool,
+        native_sol: bool,
+        deadline: Option<i64>,
     ) -> Result<()> {
-        Swap::swap(ctx, amount_in, sqrt_price_limit, min_amount_out, a_to_b)
+        Swap::swap(ctx, amount, sqrt_price_limit, min_amount_out, max_amount_in, amount_specified_is_input, a_to_b, native_sol, deadline)
     }
-}
 
+    pub fn increase_observation_cardinality(ctx: Context<IncreaseObservationCardinality>, cardinality_next: u16) -> Result<()> {
+        IncreaseObservationCardinality::increase_observation_cardinality(ctx, cardinality_next)
+    }
+
+    pub fn observe(ctx: Context<Observe>, seconds_agos: Vec<u32>) -> Result<ObserveResult> {
+        Observe::observe(ctx, seconds_agos)
+    }
+
+    pub fn initialize_reward(ctx: Context<InitializeReward>, reward_index: u8, authority: Pubkey) -> Result<()> {
+        InitializeReward::initialize_reward(ctx, reward_index, authority)
+    }
+
+    pub fn set_reward_emissions(ctx: Context<SetRewardEmissions>, reward_index: u8, emissions_per_second_x64: u128) -> Result<()> {
+        SetRewardEmissions::set_reward_emissions(ctx, reward_index, emissions_per_second_x64)
+    }
+
+    pub fn collect_reward(ctx: Context<CollectReward>, reward_index: u8) -> Result<()> {
+        CollectReward::collect_reward(ctx, reward_index)
+    }
+
+    pub fn flash_borrow(ctx: Context<FlashBorrow>, amount_a: u64, amount_b: u64) -> Result<()> {
+        FlashBorrow::flash_borrow(ctx, amount_a, amount_b)
+    }
 
+    pub fn flash_repay(ctx: Context<FlashRepay>) -> Result<()> {
+        FlashRepay::flash_repay(ctx)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn flash_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        amount: u64,
+        sqrt_price_limit: Option<u128>,
+        amount_specified_is_input: bool,
+        a_to_b: bool,
+        tick_array_count: u8,
+        callback_data: Vec<u8>,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        Swap::flash_swap(
+            ctx,
+            amount,
+            sqrt_price_limit,
+            amount_specified_is_input,
+            a_to_b,
+            tick_array_count,
+            callback_data,
+            deadline,
+        )
+    }
+
+    pub fn swap_route<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        tick_array_counts: Vec<u8>,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        SwapRoute::swap_route(ctx, amount_in, min_amount_out, tick_array_counts, deadline)
+    }
+}
@@ -0,0 +1,174 @@
+use crate::error::CLMMError;
+use anchor_lang::prelude::*;
+
+pub use big_num::{U256, U512};
+
+// kept out of this module's scope so the macro's `Result` is not anchor's
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod big_num {
+    use uint::construct_uint;
+
+    construct_uint! {
+        pub struct U256(4);
+    }
+
+    construct_uint! {
+        pub struct U512(8);
+    }
+}
+
+impl U256 {
+    fn widen(self) -> U512 {
+        let mut limbs = [0u64; 8];
+        limbs[..4].copy_from_slice(&self.0);
+        U512(limbs)
+    }
+
+    fn narrow(value: U512) -> Option<U256> {
+        if value.0[4..].iter().any(|limb| *limb != 0) {
+            return None;
+        }
+        let mut limbs = [0u64; 4];
+        limbs.copy_from_slice(&value.0[..4]);
+        Some(U256(limbs))
+    }
+
+    pub fn as_u128_checked(self) -> Option<u128> {
+        if self > U256::from(u128::MAX) {
+            None
+        } else {
+            Some(self.as_u128())
+        }
+    }
+}
+
+/// `a * b / denominator` computed on a product twice as wide as the operands,
+/// so it only fails when the quotient itself overflows or `denominator` is 0.
+pub trait MulDiv: Sized {
+    fn mul_div_floor(self, num: Self, denom: Self) -> Option<Self>;
+    fn mul_div_ceil(self, num: Self, denom: Self) -> Option<Self>;
+}
+
+impl MulDiv for u128 {
+    fn mul_div_floor(self, num: Self, denom: Self) -> Option<Self> {
+        U256::from(self)
+            .mul_div_floor(U256::from(num), U256::from(denom))?
+            .as_u128_checked()
+    }
+
+    fn mul_div_ceil(self, num: Self, denom: Self) -> Option<Self> {
+        U256::from(self)
+            .mul_div_ceil(U256::from(num), U256::from(denom))?
+            .as_u128_checked()
+    }
+}
+
+impl MulDiv for U256 {
+    fn mul_div_floor(self, num: Self, denom: Self) -> Option<Self> {
+        if denom.is_zero() {
+            return None;
+        }
+        U256::narrow(self.widen() * num.widen() / denom.widen())
+    }
+
+    fn mul_div_ceil(self, num: Self, denom: Self) -> Option<Self> {
+        if denom.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = (self.widen() * num.widen()).div_mod(denom.widen());
+        let quotient = if remainder.is_zero() {
+            quotient
+        } else {
+            quotient + U512::one()
+        };
+        U256::narrow(quotient)
+    }
+}
+
+pub fn mul_div_floor(a: u128, b: u128, denominator: u128) -> Result<u128> {
+    a.mul_div_floor(b, denominator)
+        .ok_or(CLMMError::ArithmeticOverflow.into())
+}
+
+pub fn mul_div_ceil(a: u128, b: u128, denominator: u128) -> Result<u128> {
+    a.mul_div_ceil(b, denominator)
+        .ok_or(CLMMError::ArithmeticOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{MAX_SQRT_PRICE_X64, MIN_SQRT_PRICE_X64, Q64};
+
+    fn overflow() -> Error {
+        CLMMError::ArithmeticOverflow.into()
+    }
+
+    #[test]
+    fn mul_div_rounds_down_and_up() {
+        assert_eq!(mul_div_floor(7, 3, 2).unwrap(), 10);
+        assert_eq!(mul_div_ceil(7, 3, 2).unwrap(), 11);
+        // exact quotients are the same either way
+        assert_eq!(mul_div_floor(6, 3, 2).unwrap(), 9);
+        assert_eq!(mul_div_ceil(6, 3, 2).unwrap(), 9);
+        assert_eq!(mul_div_floor(1, 1, 3).unwrap(), 0);
+        assert_eq!(mul_div_ceil(1, 1, 3).unwrap(), 1);
+    }
+
+    #[test]
+    fn mul_div_keeps_the_full_product() {
+        // u128::MAX * u128::MAX needs 256 bits, the quotient does not
+        assert_eq!(mul_div_floor(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+        assert_eq!(mul_div_ceil(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+        assert_eq!(mul_div_floor(u128::MAX, u128::MAX - 1, u128::MAX).unwrap(), u128::MAX - 1);
+        assert_eq!(mul_div_ceil(u128::MAX, Q64, Q64 + 1).unwrap(), u128::MAX - (u128::MAX >> 64));
+    }
+
+    #[test]
+    fn mul_div_overflow_is_an_error() {
+        assert_eq!(mul_div_floor(u128::MAX, 2, 1).unwrap_err(), overflow());
+        assert_eq!(mul_div_ceil(u128::MAX, 2, 1).unwrap_err(), overflow());
+        assert_eq!(mul_div_floor(1, 1, 0).unwrap_err(), overflow());
+        assert_eq!(mul_div_ceil(1, 1, 0).unwrap_err(), overflow());
+        // the same holds one size up, a quotient wider than 256 bits is None
+        assert_eq!(U256::MAX.mul_div_floor(U256::from(3), U256::from(2)), None);
+        assert_eq!(U256::MAX.mul_div_floor(U256::MAX, U256::MAX), Some(U256::MAX));
+        assert_eq!(
+            U256::MAX.mul_div_ceil(U256::MAX - U256::one(), U256::MAX),
+            Some(U256::MAX - U256::one())
+        );
+    }
+
+    #[test]
+    fn integer_sqrt_rounds_down() {
+        assert_eq!(U512::from(15u8).integer_sqrt(), U512::from(3u8));
+        assert_eq!(U512::from(16u8).integer_sqrt(), U512::from(4u8));
+        assert_eq!((U512::one() << 128).integer_sqrt(), U512::one() << 64);
+        let square = U512::from(u128::MAX) * U512::from(u128::MAX);
+        assert_eq!(square.integer_sqrt(), U512::from(u128::MAX));
+        assert_eq!((square - U512::one()).integer_sqrt(), U512::from(u128::MAX - 1));
+    }
+
+    #[test]
+    fn max_liquidity_across_the_full_price_range() {
+        let price_diff = MAX_SQRT_PRICE_X64 - MIN_SQRT_PRICE_X64;
+        // L * Q64 * (upper - lower) / upper fits the 512 bit product and the 256 bit result
+        let amount_a = (U256::from(u128::MAX) << 64)
+            .mul_div_floor(U256::from(price_diff), U256::from(MAX_SQRT_PRICE_X64))
+            .unwrap();
+        let amount_a_up = (U256::from(u128::MAX) << 64)
+            .mul_div_ceil(U256::from(price_diff), U256::from(MAX_SQRT_PRICE_X64))
+            .unwrap();
+        assert_eq!(amount_a_up, amount_a + U256::one());
+        // but the token A amount it implies is far beyond u128
+        assert_eq!(amount_a.mul_div_floor(U256::one(), U256::from(MIN_SQRT_PRICE_X64)).and_then(U256::as_u128_checked), None);
+        // token B over the same range does not fit either, and says so instead of wrapping
+        assert_eq!(mul_div_floor(u128::MAX, price_diff, Q64).unwrap_err(), overflow());
+        assert_eq!(mul_div_ceil(u128::MAX, price_diff, Q64).unwrap_err(), overflow());
+        // scaling down by the price range stays in range and rounds apart by one
+        let floor = mul_div_floor(u128::MAX, MIN_SQRT_PRICE_X64, MAX_SQRT_PRICE_X64).unwrap();
+        let ceil = mul_div_ceil(u128::MAX, MIN_SQRT_PRICE_X64, MAX_SQRT_PRICE_X64).unwrap();
+        assert!(floor > 0);
+        assert_eq!(ceil, floor + 1);
+    }
+}
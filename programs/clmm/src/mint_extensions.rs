@@ -0,0 +1,89 @@
+use crate::error::CLMMError;
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            mint_close_authority::MintCloseAuthority, transfer_fee::TransferFeeConfig,
+            BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+    },
+    token_interface::Mint,
+};
+
+/// Token-2022 mint extensions a pool can hold. Anything else either moves
+/// vault balances outside the pool's control (permanent delegate, confidential
+/// transfers), stops vaults from receiving or sending tokens (non-transferable,
+/// default frozen accounts) or needs accounts the program does not pass
+/// (transfer hooks).
+const ALLOWED_MINT_EXTENSIONS: [ExtensionType; 9] = [
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+];
+
+pub fn validate_mint_extensions(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    for extension in mint_state.get_extension_types()? {
+        require!(
+            ALLOWED_MINT_EXTENSIONS.contains(&extension),
+            CLMMError::UnsupportedMintExtension
+        );
+    }
+    Ok(())
+}
+
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(mint_state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// Account allowed to close the mint, for Token-2022 mints that have one
+pub fn mint_close_authority(mint: &InterfaceAccount<Mint>) -> Result<Option<Pubkey>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(mint_state
+        .get_extension::<MintCloseAuthority>()
+        .ok()
+        .and_then(|extension| Option::<Pubkey>::from(extension.close_authority)))
+}
+
+/// Fee the mint withholds when `pre_fee_amount` is transferred this epoch
+pub fn transfer_fee(mint: &InterfaceAccount<Mint>, pre_fee_amount: u64) -> Result<u64> {
+    let Some(config) = transfer_fee_config(mint)? else {
+        return Ok(0);
+    };
+    config
+        .calculate_epoch_fee(Clock::get()?.epoch, pre_fee_amount)
+        .ok_or(CLMMError::ArithmeticOverflow.into())
+}
+
+/// Fee to add on top of a transfer so that `post_fee_amount` arrives this epoch
+pub fn transfer_inverse_fee(mint: &InterfaceAccount<Mint>, post_fee_amount: u64) -> Result<u64> {
+    let Some(config) = transfer_fee_config(mint)? else {
+        return Ok(0);
+    };
+    config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, post_fee_amount)
+        .ok_or(CLMMError::ArithmeticOverflow.into())
+}
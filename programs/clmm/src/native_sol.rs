@@ -0,0 +1,112 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::program_pack::Pack,
+    system_program::{allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer},
+};
+use anchor_spl::token_interface::{
+    close_account, initialize_account3, sync_native, CloseAccount, InitializeAccount3, Mint, SyncNative, TokenInterface,
+};
+
+use crate::error::CLMMError;
+
+pub use anchor_spl::token::spl_token::native_mint::ID as NATIVE_MINT;
+
+/// Seed of the ephemeral wSOL account at `[WSOL_SEED, owner]` that carries an
+/// owner's lamports through a single instruction.
+pub const WSOL_SEED: &[u8] = b"wsol";
+
+pub fn is_native_mint(mint: &InterfaceAccount<Mint>) -> bool {
+    mint.key() == NATIVE_MINT
+}
+
+/// Creates `owner`'s ephemeral wSOL account and wraps `amount` of their
+/// lamports into it. It stands in for their token account on the native side,
+/// so whatever wSOL they already hold is left alone, and has to be closed with
+/// `unwrap_sol` before the instruction ends.
+pub fn open_wsol<'info>(
+    owner: &Signer<'info>,
+    wsol_account: &Option<UncheckedAccount<'info>>,
+    mint: &InterfaceAccount<'info, Mint>,
+    amount: u64,
+    system_program: &Program<'info, System>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<AccountInfo<'info>> {
+    let wsol_account = wsol_account.as_ref().ok_or(CLMMError::InvalidWsolAccount)?.to_account_info();
+    let (address, bump) = Pubkey::find_program_address(&[WSOL_SEED, owner.key.as_ref()], &crate::ID);
+    require_keys_eq!(wsol_account.key(), address, CLMMError::InvalidWsolAccount);
+    let signer_seeds: &[&[&[u8]]] = &[&[WSOL_SEED, owner.key.as_ref(), &[bump]]];
+    let space = anchor_spl::token::spl_token::state::Account::LEN;
+    let rent = Rent::get()?.minimum_balance(space);
+    let current_lamports = wsol_account.lamports();
+    if current_lamports == 0 {
+        let accounts = CreateAccount {
+            from: owner.to_account_info(),
+            to: wsol_account.clone(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), accounts, signer_seeds);
+        create_account(cpi_ctx, rent, space as u64, token_program.key)?;
+    } else {
+        // anyone can send lamports to the address, which would make create_account fail
+        if current_lamports < rent {
+            let accounts = Transfer {
+                from: owner.to_account_info(),
+                to: wsol_account.clone(),
+            };
+            transfer(CpiContext::new(system_program.to_account_info(), accounts), rent - current_lamports)?;
+        }
+        let accounts = Allocate {
+            account_to_allocate: wsol_account.clone(),
+        };
+        allocate(CpiContext::new_with_signer(system_program.to_account_info(), accounts, signer_seeds), space as u64)?;
+        let accounts = Assign {
+            account_to_assign: wsol_account.clone(),
+        };
+        assign(CpiContext::new_with_signer(system_program.to_account_info(), accounts, signer_seeds), token_program.key)?;
+    }
+    let accounts = InitializeAccount3 {
+        account: wsol_account.clone(),
+        mint: mint.to_account_info(),
+        authority: owner.to_account_info(),
+    };
+    initialize_account3(CpiContext::new(token_program.to_account_info(), accounts))?;
+    wrap_sol(owner, &wsol_account, amount, system_program, token_program)?;
+    Ok(wsol_account)
+}
+
+/// Moves `amount` lamports from `owner` into a wSOL account and syncs its
+/// token balance, so the account can pay `amount` in the same instruction.
+pub fn wrap_sol<'info>(
+    owner: &Signer<'info>,
+    token_account: &AccountInfo<'info>,
+    amount: u64,
+    system_program: &Program<'info, System>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let accounts = Transfer {
+        from: owner.to_account_info(),
+        to: token_account.clone(),
+    };
+    transfer(CpiContext::new(system_program.to_account_info(), accounts), amount)?;
+    let accounts = SyncNative {
+        account: token_account.clone(),
+    };
+    sync_native(CpiContext::new(token_program.to_account_info(), accounts))
+}
+
+/// Closes `owner`'s ephemeral wSOL account, returning its balance and rent
+/// to them as lamports.
+pub fn unwrap_sol<'info>(
+    owner: &Signer<'info>,
+    wsol_account: &AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let accounts = CloseAccount {
+        account: wsol_account.clone(),
+        destination: owner.to_account_info(),
+        authority: owner.to_account_info(),
+    };
+    close_account(CpiContext::new(token_program.to_account_info(), accounts))
+}
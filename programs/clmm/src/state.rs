@@ -1,27 +1,531 @@
 use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, math::mul_div_floor, utils::{FEE_RATE_DENOMINATOR, MAX_FEE_TIERS, NUM_REWARDS, OBSERVATION_CAPACITY, Q64, TICK_ARRAY_BITMAP_EXTENSION_WORDS, TICK_ARRAY_SIZE}};
+
+/// Program-wide settings every pool is created against, a single PDA owned by `admin`
+#[derive(Debug,InitSpace)]
+#[account]
+pub struct AmmConfig{
+    pub admin:Pubkey,
+    /// set by `transfer_admin`, becomes `admin` once it signs `accept_admin`
+    pub pending_admin:Pubkey,
+    /// owner of the token accounts protocol fees are paid to
+    pub treasury:Pubkey,
+    /// fee tiers pools may be created with
+    #[max_len(MAX_FEE_TIERS)]
+    pub fee_tiers:Vec<FeeTier>,
+    pub bump:u8,
+}
+
+/// Starting price of a new pool
+#[derive(AnchorSerialize,AnchorDeserialize,Clone,Copy,Debug)]
+pub enum InitialPrice{
+    /// exact sqrt price in Q64.64
+    SqrtPriceX64(u128),
+    /// whole token B per whole token A, adjusted for the mints' decimals
+    Ratio{numerator:u64,denominator:u64},
+}
+
+#[derive(AnchorSerialize,AnchorDeserialize,Clone,Copy,Debug,InitSpace)]
+pub struct FeeTier{
+    /// swap fee in hundredths of a bip
+    pub fee_rate:u32,
+    pub tick_spacing:u16,
+    /// share of each swap fee kept by the protocol, in hundredths of a bip of the fee
+    pub protocol_fee_rate:u32,
+}
+
+impl AmmConfig{
+    pub fn fee_tier(&self,fee_rate:u32)->Result<&FeeTier>{
+        self.fee_tiers.iter().find(|tier|tier.fee_rate==fee_rate).ok_or(CLMMError::InvalidFeeTier.into())
+    }
+
+    /// Rejects tier lists that are too long, repeat a fee rate or hold rates
+    /// or spacings a pool could not work with.
+    pub fn validate_fee_tiers(fee_tiers:&[FeeTier])->Result<()>{
+        require!(fee_tiers.len()<=MAX_FEE_TIERS,CLMMError::InvalidFeeTier);
+        for (i,tier) in fee_tiers.iter().enumerate(){
+            require!(tier.fee_rate<FEE_RATE_DENOMINATOR,CLMMError::InvalidFeeTier);
+            require!(tier.protocol_fee_rate<=FEE_RATE_DENOMINATOR,CLMMError::InvalidFeeTier);
+            require!(tier.tick_spacing>0,CLMMError::InvalidFeeTier);
+            require!(fee_tiers[..i].iter().all(|other|other.fee_rate!=tier.fee_rate),CLMMError::InvalidFeeTier);
+        }
+        Ok(())
+    }
+}
 #[derive(Debug,InitSpace)]
 #[account]
 pub struct Pool{
     pub minta:Pubkey,
     pub mintb:Pubkey,
-    pub lp_mint:Pubkey,
-    pub pool_authority:Pubkey,
+    /// `AmmConfig` whose admin collects the protocol fees
+    pub amm_config:Pubkey,
     pub sqrt_price:u128,
-    pub active_liquidity:u128,
-    pub total_lp_liqidity:u128,
     pub active_liqiudity:u128,
-    pub seed:u64,
-    pub total_lp_issued:u64,
+    /// swap fees earned per unit of in-range liquidity over the pool's life, Q64.64
+    pub fee_growth_global_a:u128,
+    pub fee_growth_global_b:u128,
+    pub protocol_fees_owed_a:u64,
+    pub protocol_fees_owed_b:u64,
     pub current_tick:i32,
+    /// swap fee in hundredths of a bip, taken from the input of every step
+    pub fee_rate:u32,
+    /// copied from the fee tier when the pool is created
+    pub protocol_fee_rate:u32,
+    pub tick_spacing:u16,
     pub bump:u8,
-    pub padding:[u8;3],
+    /// set while a flash loan is outstanding, nothing else may touch the vaults
+    pub locked:bool,
+    pub padding:[u8;2],
+    /// one bit per tick array, set while the array holds at least one initialized tick;
+    /// arrays further from tick 0 are tracked by the pool's `TickArrayBitmapExtension`
+    pub tick_array_bitmap:[u64;16],
+    /// liquidity mining rewards, filled in order by `initialize_reward`
+    pub reward_infos:[RewardInfo;NUM_REWARDS],
+    /// when the reward growths were last brought up to date
+    pub reward_last_updated_timestamp:u32,
+    /// amounts lent out by the outstanding flash loan
+    pub flash_loan_amount_a:u64,
+    pub flash_loan_amount_b:u64,
 }
+
+/// Signer seeds of a pool's PDA, for the CPIs it signs as authority of its
+/// vaults and position mints. Bind it with `let` so the seeds live as long as
+/// the binding does.
+macro_rules! pool_signer_seeds{
+    ($pool:expr)=>{
+        &[&[&b"pool"[..],$pool.amm_config.as_ref(),$pool.minta.as_ref(),$pool.mintb.as_ref(),&$pool.fee_rate.to_le_bytes(),&[$pool.bump]] as &[&[u8]]]
+    };
+}
+pub(crate) use pool_signer_seeds;
+
+impl Pool{
+    /// Shares out each reward's emissions since the last update among the
+    /// liquidity in range. Nobody earns what is emitted while none is.
+    pub fn update_rewards(&mut self,now:u32)->Result<()>{
+        let elapsed=now.saturating_sub(self.reward_last_updated_timestamp);
+        let liquidity=self.active_liqiudity;
+        if elapsed>0 && liquidity>0{
+            for reward in self.reward_infos.iter_mut().filter(|reward|reward.initialized()){
+                let emitted_x64=reward.emissions_per_second_x64.checked_mul(elapsed as u128).ok_or(CLMMError::ArithmeticOverflow)?;
+                reward.growth_global_x64=reward.growth_global_x64.wrapping_add(emitted_x64/liquidity);
+            }
+        }
+        self.reward_last_updated_timestamp=now;
+        Ok(())
+    }
+
+    /// Shares a fee paid outside of a swap like a swap fee: the protocol takes
+    /// its cut and the rest goes to the liquidity in range, or to the protocol
+    /// too when there is none.
+    pub fn credit_fee(&mut self,fee:u64,token_a:bool)->Result<()>{
+        let fee=fee as u128;
+        let mut protocol_fee=fee.checked_mul(self.protocol_fee_rate as u128).ok_or(CLMMError::ArithmeticOverflow)?/FEE_RATE_DENOMINATOR as u128;
+        let lp_fee=fee-protocol_fee;
+        match lp_fee.checked_mul(Q64).ok_or(CLMMError::ArithmeticOverflow)?.checked_div(self.active_liqiudity){
+            Some(fee_growth)=>{
+                if token_a{
+                    self.fee_growth_global_a=self.fee_growth_global_a.wrapping_add(fee_growth);
+                }else{
+                    self.fee_growth_global_b=self.fee_growth_global_b.wrapping_add(fee_growth);
+                }
+            }
+            None=>protocol_fee=fee,
+        }
+        let protocol_fee=protocol_fee as u64;
+        if token_a{
+            self.protocol_fees_owed_a=self.protocol_fees_owed_a.checked_add(protocol_fee).ok_or(CLMMError::ArithmeticOverflow)?;
+        }else{
+            self.protocol_fees_owed_b=self.protocol_fees_owed_b.checked_add(protocol_fee).ok_or(CLMMError::ArithmeticOverflow)?;
+        }
+        Ok(())
+    }
+
+    pub fn reward_growths_global(&self)->[u128;NUM_REWARDS]{
+        self.reward_infos.map(|reward|reward.growth_global_x64)
+    }
+}
+
+/// A token emitted to in-range liquidity out of `vault` at a set rate
+#[derive(AnchorSerialize,AnchorDeserialize,Clone,Copy,Debug,Default,InitSpace)]
+pub struct RewardInfo{
+    pub mint:Pubkey,
+    pub vault:Pubkey,
+    /// may change the emission rate
+    pub authority:Pubkey,
+    /// tokens emitted per second, Q64.64
+    pub emissions_per_second_x64:u128,
+    /// rewards emitted per unit of in-range liquidity over the reward's life, Q64.64
+    pub growth_global_x64:u128,
+}
+
+impl RewardInfo{
+    pub fn initialized(&self)->bool{
+        self.mint!=Pubkey::default()
+    }
+}
+
+/// A liquidity range owned by whoever holds the single token of `position_mint`
 #[derive(Debug,InitSpace)]
 #[account]
+pub struct Position{
+    pub pool:Pubkey,
+    pub position_mint:Pubkey,
+    pub liquidity:u128,
+    /// fee growth inside the range when the position was last updated
+    pub fee_growth_inside_last_a:u128,
+    pub fee_growth_inside_last_b:u128,
+    pub tokens_owed_a:u64,
+    pub tokens_owed_b:u64,
+    pub tick_lower:i32,
+    pub tick_upper:i32,
+    pub bump:u8,
+    pub reward_infos:[PositionRewardInfo;NUM_REWARDS],
+}
+
+#[derive(AnchorSerialize,AnchorDeserialize,Clone,Copy,Debug,Default,InitSpace)]
+pub struct PositionRewardInfo{
+    /// reward growth inside the range when the position was last updated
+    pub growth_inside_last_x64:u128,
+    pub amount_owed:u64,
+}
+
+impl Position{
+    /// Credits the rewards earned since the last checkpoint to `amount_owed`,
+    /// to be called before any change to the position's liquidity.
+    pub fn update_rewards(&mut self,reward_growths_inside:[u128;NUM_REWARDS])->Result<()>{
+        for (reward,growth_inside) in self.reward_infos.iter_mut().zip(reward_growths_inside){
+            let owed=mul_div_floor(growth_inside.wrapping_sub(reward.growth_inside_last_x64),self.liquidity,Q64)?;
+            reward.amount_owed=reward.amount_owed.wrapping_add(owed as u64);
+            reward.growth_inside_last_x64=growth_inside;
+        }
+        Ok(())
+    }
+
+    /// Credits the fees earned since the last checkpoint to `tokens_owed`
+    /// before applying the liquidity change.
+    pub fn update(&mut self,liquidity_delta:i128,fee_growth_inside_a:u128,fee_growth_inside_b:u128)->Result<()>{
+        let owed_a=mul_div_floor(fee_growth_inside_a.wrapping_sub(self.fee_growth_inside_last_a),self.liquidity,Q64)?;
+        let owed_b=mul_div_floor(fee_growth_inside_b.wrapping_sub(self.fee_growth_inside_last_b),self.liquidity,Q64)?;
+        // owed amounts wrap like Uniswap's, the position has to collect before they overflow
+        self.tokens_owed_a=self.tokens_owed_a.wrapping_add(owed_a as u64);
+        self.tokens_owed_b=self.tokens_owed_b.wrapping_add(owed_b as u64);
+        self.fee_growth_inside_last_a=fee_growth_inside_a;
+        self.fee_growth_inside_last_b=fee_growth_inside_b;
+        self.liquidity=if liquidity_delta>=0{
+            self.liquidity.checked_add(liquidity_delta as u128)
+        }else{
+            self.liquidity.checked_sub(liquidity_delta.unsigned_abs())
+        }.ok_or(CLMMError::InsufficientPositionLiquidity)?;
+        Ok(())
+    }
+}
+
+#[zero_copy]
+#[derive(Debug,InitSpace)]
+pub struct TickState{
+    pub liquidity_net:i128,
+    pub liquidity_gross:u128,
+    /// fee growth on the other side of this tick from the current price
+    pub fee_growth_outside_a:u128,
+    pub fee_growth_outside_b:u128,
+    /// reward growth on the other side of this tick, per reward slot
+    pub reward_growths_outside_x64:[u128;NUM_REWARDS],
+    pub tick:i32,
+    pub padding:[u8;12],
+}
 
-pub struct  tick{
-    pub sqrt_price_x64:u128,
-    pub liquidity:i128,
-    pub index:i32,
-    pub bump:u8
-}
\ No newline at end of file
+impl TickState{
+    pub fn is_initialized(&self)->bool{
+        self.liquidity_gross!=0
+    }
+
+    /// Applies a liquidity change for a position bounded by this tick and
+    /// returns true when the tick flips between initialized and uninitialized.
+    pub fn update(&mut self,liquidity_delta:i128,upper:bool,current_tick:i32,fee_growth_global_a:u128,fee_growth_global_b:u128,reward_growths_global:&[u128;NUM_REWARDS])->Result<bool>{
+        let gross_before=self.liquidity_gross;
+        if gross_before==0{
+            // by convention all growth so far happened below a newly initialized tick
+            (self.fee_growth_outside_a,self.fee_growth_outside_b,self.reward_growths_outside_x64)=if self.tick<=current_tick{
+                (fee_growth_global_a,fee_growth_global_b,*reward_growths_global)
+            }else{
+                (0,0,[0;NUM_REWARDS])
+            };
+        }
+        self.liquidity_gross=if liquidity_delta>=0{
+            gross_before.checked_add(liquidity_delta as u128)
+        }else{
+            gross_before.checked_sub(liquidity_delta.unsigned_abs())
+        }.ok_or(CLMMError::ArithmeticOverflow)?;
+        // crossing the lower tick left to right adds liquidity, crossing the upper removes it
+        self.liquidity_net=if upper{
+            self.liquidity_net.checked_sub(liquidity_delta)
+        }else{
+            self.liquidity_net.checked_add(liquidity_delta)
+        }.ok_or(CLMMError::ArithmeticOverflow)?;
+        Ok((gross_before==0)!=(self.liquidity_gross==0))
+    }
+
+    /// Flips the outside fee and reward growth when the price moves across
+    /// this tick and returns its net liquidity.
+    pub fn cross(&mut self,fee_growth_global_a:u128,fee_growth_global_b:u128,reward_growths_global:&[u128;NUM_REWARDS])->i128{
+        self.fee_growth_outside_a=fee_growth_global_a.wrapping_sub(self.fee_growth_outside_a);
+        self.fee_growth_outside_b=fee_growth_global_b.wrapping_sub(self.fee_growth_outside_b);
+        for (outside,global) in self.reward_growths_outside_x64.iter_mut().zip(reward_growths_global){
+            *outside=global.wrapping_sub(*outside);
+        }
+        self.liquidity_net
+    }
+
+    /// Fee growth per unit of liquidity between `lower` and `upper`.
+    pub fn fee_growth_inside(lower:&TickState,upper:&TickState,current_tick:i32,fee_growth_global_a:u128,fee_growth_global_b:u128)->(u128,u128){
+        let (below_a,below_b)=if current_tick>=lower.tick{
+            (lower.fee_growth_outside_a,lower.fee_growth_outside_b)
+        }else{
+            (fee_growth_global_a.wrapping_sub(lower.fee_growth_outside_a),fee_growth_global_b.wrapping_sub(lower.fee_growth_outside_b))
+        };
+        let (above_a,above_b)=if current_tick<upper.tick{
+            (upper.fee_growth_outside_a,upper.fee_growth_outside_b)
+        }else{
+            (fee_growth_global_a.wrapping_sub(upper.fee_growth_outside_a),fee_growth_global_b.wrapping_sub(upper.fee_growth_outside_b))
+        };
+        (
+            fee_growth_global_a.wrapping_sub(below_a).wrapping_sub(above_a),
+            fee_growth_global_b.wrapping_sub(below_b).wrapping_sub(above_b),
+        )
+    }
+
+    /// Reward growth per unit of liquidity between `lower` and `upper`, per reward slot.
+    pub fn reward_growths_inside(lower:&TickState,upper:&TickState,current_tick:i32,reward_growths_global:&[u128;NUM_REWARDS])->[u128;NUM_REWARDS]{
+        let mut inside=[0;NUM_REWARDS];
+        for (i,global) in reward_growths_global.iter().enumerate(){
+            let below=if current_tick>=lower.tick{
+                lower.reward_growths_outside_x64[i]
+            }else{
+                global.wrapping_sub(lower.reward_growths_outside_x64[i])
+            };
+            let above=if current_tick<upper.tick{
+                upper.reward_growths_outside_x64[i]
+            }else{
+                global.wrapping_sub(upper.reward_growths_outside_x64[i])
+            };
+            inside[i]=global.wrapping_sub(below).wrapping_sub(above);
+        }
+        inside
+    }
+}
+
+#[account(zero_copy)]
+#[derive(Debug,InitSpace)]
+pub struct TickArray{
+    pub pool:Pubkey,
+    pub start_tick_index:i32,
+    pub initialized_tick_count:u8,
+    pub padding:[u8;11],
+    pub ticks:[TickState;TICK_ARRAY_SIZE as usize],
+}
+
+impl TickArray{
+    fn tick_offset(&self,tick_index:i32,tick_spacing:i32)->Result<usize>{
+        let offset=tick_index.checked_sub(self.start_tick_index).ok_or(CLMMError::ArithmeticOverflow)?;
+        require!(offset>=0 && offset<TICK_ARRAY_SIZE*tick_spacing,CLMMError::InvalidTickArray);
+        require!(offset%tick_spacing==0,CLMMError::UnalignedTick);
+        Ok((offset/tick_spacing) as usize)
+    }
+
+    pub fn get_tick(&self,tick_index:i32,tick_spacing:i32)->Result<&TickState>{
+        Ok(&self.ticks[self.tick_offset(tick_index,tick_spacing)?])
+    }
+
+    pub fn get_tick_mut(&mut self,tick_index:i32,tick_spacing:i32)->Result<&mut TickState>{
+        let offset=self.tick_offset(tick_index,tick_spacing)?;
+        Ok(&mut self.ticks[offset])
+    }
+
+    /// Next initialized tick in the swap direction: the highest one at or below
+    /// `current_tick` when a_to_b, otherwise the lowest one above it.
+    pub fn next_initialized_tick(&self,current_tick:i32,a_to_b:bool)->Option<&TickState>{
+        if a_to_b{
+            self.ticks.iter().rev().find(|t|t.is_initialized() && t.tick<=current_tick)
+        }else{
+            self.ticks.iter().find(|t|t.is_initialized() && t.tick>current_tick)
+        }
+    }
+}
+
+/// Bits for the tick arrays beyond either end of `Pool::tick_array_bitmap`,
+/// so pools with a small tick spacing can use the whole tick range.
+#[account(zero_copy)]
+#[derive(Debug,InitSpace)]
+pub struct TickArrayBitmapExtension{
+    pub pool:Pubkey,
+    /// arrays below the pool bitmap, lowest first
+    pub negative_bitmap:[u64;TICK_ARRAY_BITMAP_EXTENSION_WORDS],
+    /// arrays above the pool bitmap, lowest first
+    pub positive_bitmap:[u64;TICK_ARRAY_BITMAP_EXTENSION_WORDS],
+}
+
+/// Cumulative tick and liquidity values at `block_timestamp`. The difference
+/// between two observations divided by the seconds between them gives the
+/// time-weighted average tick and in-range liquidity over that window.
+#[zero_copy]
+#[derive(Debug,InitSpace)]
+pub struct Observation{
+    pub block_timestamp:u32,
+    pub initialized:u8,
+    pub padding:[u8;3],
+    /// sum of the current tick over every second since the pool was created
+    pub tick_cumulative:i64,
+    /// sum of seconds divided by in-range liquidity, Q64.64
+    pub seconds_per_liquidity_cumulative_x64:u128,
+}
+
+impl Observation{
+    /// The observation at `block_timestamp`, assuming `tick` and `liquidity`
+    /// held ever since this one.
+    fn transform(&self,block_timestamp:u32,tick:i32,liquidity:u128)->Observation{
+        let delta=block_timestamp.wrapping_sub(self.block_timestamp);
+        Observation{
+            block_timestamp,
+            initialized:1,
+            padding:[0;3],
+            tick_cumulative:self.tick_cumulative.wrapping_add(tick as i64*delta as i64),
+            seconds_per_liquidity_cumulative_x64:self.seconds_per_liquidity_cumulative_x64.wrapping_add(((delta as u128)<<64)/liquidity.max(1)),
+        }
+    }
+}
+
+/// Ring buffer of a pool's observations, written by the first swap or in-range
+/// liquidity change of every new timestamp. Only the first
+/// `observation_cardinality` entries are in use.
+#[account(zero_copy)]
+#[derive(Debug,InitSpace)]
+pub struct ObservationState{
+    pub pool:Pubkey,
+    /// most recently written entry
+    pub observation_index:u16,
+    pub observation_cardinality:u16,
+    /// size the buffer grows to once the index next reaches its current end
+    pub observation_cardinality_next:u16,
+    pub padding:[u8;10],
+    pub observations:[Observation;OBSERVATION_CAPACITY],
+}
+
+/// Cumulative values returned by `observe`, one entry per requested `seconds_ago`
+#[derive(AnchorSerialize,AnchorDeserialize,Clone,Debug)]
+pub struct ObserveResult{
+    pub tick_cumulatives:Vec<i64>,
+    pub seconds_per_liquidity_cumulatives_x64:Vec<u128>,
+}
+
+impl ObservationState{
+    pub fn initialize(&mut self,pool:Pubkey,block_timestamp:u32){
+        self.pool=pool;
+        self.observation_index=0;
+        self.observation_cardinality=1;
+        self.observation_cardinality_next=1;
+        self.observations[0]=Observation{
+            block_timestamp,
+            initialized:1,
+            padding:[0;3],
+            tick_cumulative:0,
+            seconds_per_liquidity_cumulative_x64:0,
+        };
+    }
+
+    /// Records what the pool held since the last observation, at most once
+    /// per timestamp.
+    pub fn write(&mut self,block_timestamp:u32,tick:i32,liquidity:u128){
+        let index=self.observation_index as usize;
+        let last=self.observations[index];
+        if last.block_timestamp==block_timestamp{
+            return;
+        }
+        let cardinality=if self.observation_cardinality_next>self.observation_cardinality && index==self.observation_cardinality as usize-1{
+            self.observation_cardinality_next
+        }else{
+            self.observation_cardinality
+        };
+        let next=(index+1)%cardinality as usize;
+        self.observations[next]=last.transform(block_timestamp,tick,liquidity);
+        self.observation_index=next as u16;
+        self.observation_cardinality=cardinality;
+    }
+
+    pub fn grow(&mut self,cardinality_next:u16)->Result<()>{
+        require!(cardinality_next>self.observation_cardinality_next && cardinality_next as usize<=OBSERVATION_CAPACITY,CLMMError::InvalidObservationCardinality);
+        self.observation_cardinality_next=cardinality_next;
+        Ok(())
+    }
+
+    pub fn observe(&self,time:u32,seconds_agos:&[u32],tick:i32,liquidity:u128)->Result<ObserveResult>{
+        let mut result=ObserveResult{
+            tick_cumulatives:Vec::with_capacity(seconds_agos.len()),
+            seconds_per_liquidity_cumulatives_x64:Vec::with_capacity(seconds_agos.len()),
+        };
+        for seconds_ago in seconds_agos{
+            let observation=self.observe_single(time,*seconds_ago,tick,liquidity)?;
+            result.tick_cumulatives.push(observation.tick_cumulative);
+            result.seconds_per_liquidity_cumulatives_x64.push(observation.seconds_per_liquidity_cumulative_x64);
+        }
+        Ok(result)
+    }
+
+    /// Cumulative values `seconds_ago` before `time`, interpolated between the
+    /// observations on either side or extrapolated past the newest one.
+    fn observe_single(&self,time:u32,seconds_ago:u32,tick:i32,liquidity:u128)->Result<Observation>{
+        let target=time.checked_sub(seconds_ago).ok_or(CLMMError::ObservationTooOld)?;
+        let (before,at_or_after)=self.surrounding_observations(target,tick,liquidity)?;
+        if target==before.block_timestamp{
+            return Ok(before);
+        }
+        if target==at_or_after.block_timestamp{
+            return Ok(at_or_after);
+        }
+        let observation_delta=at_or_after.block_timestamp-before.block_timestamp;
+        let target_delta=target-before.block_timestamp;
+        let tick_delta=at_or_after.tick_cumulative.wrapping_sub(before.tick_cumulative);
+        let seconds_per_liquidity_delta=at_or_after.seconds_per_liquidity_cumulative_x64.wrapping_sub(before.seconds_per_liquidity_cumulative_x64);
+        Ok(Observation{
+            block_timestamp:target,
+            initialized:1,
+            padding:[0;3],
+            tick_cumulative:before.tick_cumulative.wrapping_add(tick_delta/observation_delta as i64*target_delta as i64),
+            seconds_per_liquidity_cumulative_x64:before.seconds_per_liquidity_cumulative_x64.wrapping_add(mul_div_floor(seconds_per_liquidity_delta,target_delta as u128,observation_delta as u128)?),
+        })
+    }
+
+    /// The observations at or just before and at or just after `target`.
+    fn surrounding_observations(&self,target:u32,tick:i32,liquidity:u128)->Result<(Observation,Observation)>{
+        let index=self.observation_index as usize;
+        let cardinality=self.observation_cardinality as usize;
+        let newest=self.observations[index];
+        if newest.block_timestamp<=target{
+            return Ok((newest,newest.transform(target,tick,liquidity)));
+        }
+        // the oldest entry follows the newest, unless the buffer has not wrapped yet
+        let mut oldest=self.observations[(index+1)%cardinality];
+        if oldest.initialized==0{
+            oldest=self.observations[0];
+        }
+        require!(oldest.block_timestamp<=target,CLMMError::ObservationTooOld);
+        let mut left=(index+1)%cardinality;
+        let mut right=left+cardinality-1;
+        loop{
+            let i=(left+right)/2;
+            let before=self.observations[i%cardinality];
+            if before.initialized==0{
+                left=i+1;
+                continue;
+            }
+            let at_or_after=self.observations[(i+1)%cardinality];
+            if before.block_timestamp>target{
+                right=i-1;
+            }else if at_or_after.block_timestamp<target{
+                left=i+1;
+            }else{
+                return Ok((before,at_or_after));
+            }
+        }
+    }
+}
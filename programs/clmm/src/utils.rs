@@ -1,8 +1,21 @@
 use crate::error::CLMMError;
+use crate::math::{mul_div_ceil, mul_div_floor, MulDiv, U256, U512};
+use crate::state::TickArrayBitmapExtension;
 use anchor_lang::prelude::*;
 
-const Q64: u128 = 1 << 64;
-pub const TICK_SPACING: i32 = 10;
+pub const Q64: u128 = 1 << 64;
+/// Fee rates are expressed in hundredths of a bip, so 1_000_000 is 100%
+pub const FEE_RATE_DENOMINATOR: u32 = 1_000_000;
+/// Number of ticks (of the pool's spacing) stored in one `TickArray` account
+pub const TICK_ARRAY_SIZE: i32 = 60;
+/// Tick arrays tracked by `Pool::tick_array_bitmap` on each side of tick 0
+pub const TICK_ARRAY_BITMAP_HALF: i32 = 512;
+/// Tick arrays tracked by `TickArrayBitmapExtension` past each end of the pool
+/// bitmap, enough to reach MIN_TICK and MAX_TICK at a tick spacing of 1
+pub const TICK_ARRAY_BITMAP_EXTENSION_HALF: i32 = 7168;
+pub const TICK_ARRAY_BITMAP_EXTENSION_WORDS: usize = (TICK_ARRAY_BITMAP_EXTENSION_HALF / 64) as usize;
+const TICK_ARRAY_BITMAP_TOTAL_HALF: i32 = TICK_ARRAY_BITMAP_HALF + TICK_ARRAY_BITMAP_EXTENSION_HALF;
+const TICK_ARRAY_BITMAP_WORDS: usize = 2 * TICK_ARRAY_BITMAP_EXTENSION_WORDS + 16;
 const BASE_SQRT_PRICE_X64: u128 = Q64;
 
 const SQRT_1_0001_X64: u128 = 18446758646477570048; // sqrt(1.0001) * 2^64
@@ -14,42 +27,64 @@ pub const MIN_SQRT_PRICE_X64: u128 = 4295048016;
 pub const MAX_SQRT_PRICE_X64: u128 = 79226673521066979257578248091;
 const BIT_PRECISION: u32 = 16;
 
-pub fn integer_sqrt(value: u128) -> u64 {
-    if value == 0 {
-        return 0;
+/// Most fee tiers an `AmmConfig` can allow at once
+pub const MAX_FEE_TIERS: usize = 8;
+
+/// Reward slots every pool has
+pub const NUM_REWARDS: usize = 3;
+/// Emissions a reward vault must hold in advance when its rate is set
+pub const REWARD_FUNDED_SECONDS: u128 = 86_400;
+
+/// Size of a pool's observation ring buffer, the most its cardinality can grow to
+pub const OBSERVATION_CAPACITY: usize = 100;
+/// Most `seconds_agos` one `observe` can answer within the return data limit
+pub const MAX_OBSERVE_LEN: usize = 40;
+
+/// Fails once the cluster clock has passed `deadline`, a unix timestamp
+pub fn check_deadline(deadline: Option<i64>) -> Result<()> {
+    if let Some(deadline) = deadline {
+        require!(
+            Clock::get()?.unix_timestamp <= deadline,
+            CLMMError::TransactionExpired
+        );
     }
-
-    let mut x = value;
-    let mut y = (value + 1) / 2;
-
-    while y < x {
-        x = y;
-        y = (x + value / x) / 2;
-    }
-
-    x as u64
+    Ok(())
 }
 
-/// Convert a u64 price to sqrt_price_x64 format
-/// Formula: sqrt(price) * 2^64
-pub fn price_to_sqrt_price_x64(price: u64) -> Result<u128> {
-    if price == 0 {
-        return Err(CLMMError::ZeroAmount.into());
-    }
-
-    let price_scaled = price as u128;
-
-    let mut x = price_scaled;
-    let mut y = (price_scaled + 1) / 2;
-
-    while y < x {
-        x = y;
-        y = (x + price_scaled / x) / 2;
-    }
-
-    let sqrt_price_x64 = x.checked_mul(Q64).ok_or(CLMMError::ArithmeticOverflow)?;
+/// Cluster clock as the 32-bit timestamp observations are keyed by
+pub fn block_timestamp() -> Result<u32> {
+    u32::try_from(Clock::get()?.unix_timestamp).map_err(|_| CLMMError::ArithmeticOverflow.into())
+}
 
-    Ok(sqrt_price_x64)
+/// Sqrt price, Q64.64, for a price of `numerator / denominator` whole
+/// token B per whole token A, scaled to the mints' smallest units:
+/// sqrt(numerator * 10^decimals_b / (denominator * 10^decimals_a)) * 2^64
+pub fn price_to_sqrt_price_x64(numerator: u64, denominator: u64, decimals_a: u8, decimals_b: u8) -> Result<u128> {
+    require!(numerator > 0 && denominator > 0, CLMMError::ZeroAmount);
+    let (scale_numerator, scale_denominator) = if decimals_b >= decimals_a {
+        (decimals_b - decimals_a, 0)
+    } else {
+        (0, decimals_a - decimals_b)
+    };
+    let exp10 = |exponent: u8| U512::from(10u8).checked_pow(U512::from(exponent));
+    let price_numerator = exp10(scale_numerator)
+        .and_then(|scale| scale.checked_mul(U512::from(numerator)))
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+    let price_denominator = exp10(scale_denominator)
+        .and_then(|scale| scale.checked_mul(U512::from(denominator)))
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+    // the root of price * 2^128 is the price's root in Q64.64
+    let sqrt_price_x64 = price_numerator
+        .checked_mul(U512::one() << 128)
+        .ok_or(CLMMError::ArithmeticOverflow)?
+        .checked_div(price_denominator)
+        .ok_or(CLMMError::ArithmeticOverflow)?
+        .integer_sqrt();
+    require!(
+        sqrt_price_x64 >= U512::from(MIN_SQRT_PRICE_X64) && sqrt_price_x64 < U512::from(MAX_SQRT_PRICE_X64),
+        CLMMError::SqrtPriceX64
+    );
+    Ok(sqrt_price_x64.low_u128())
 }
 
 // formula = sqrt(log(1.0001^tick)) * 2^64
@@ -205,202 +240,497 @@ pub fn sqrt_price_x64_to_tick(sqrt_price_x64: u128) -> Result<i32> {
     })
 }
 
+/// Token amounts backing `liquidity` at the current price, rounded up when
+/// they are paid into the pool and down when they are paid out of it.
 pub fn calculate_liquidity_amounts(
     sqrt_price_current_x64: u128,
     sqrt_price_lower_x64: u128,
     sqrt_price_upper_x64: u128,
     liquidity: u128,
+    round_up: bool,
 ) -> Result<(u64, u64)> {
-    let amount_a: u64;
-    let amount_b: u64;
+    let (amount_a, amount_b) = if sqrt_price_current_x64 <= sqrt_price_lower_x64 {
+        // Token A only
+        (get_amount_a_delta(sqrt_price_lower_x64, sqrt_price_upper_x64, liquidity, round_up)?, 0)
+    } else if sqrt_price_current_x64 >= sqrt_price_upper_x64 {
+        // Token B only
+        (0, get_amount_b_delta(sqrt_price_lower_x64, sqrt_price_upper_x64, liquidity, round_up)?)
+    } else {
+        // Both tokens, A above the current price and B below it
+        (
+            get_amount_a_delta(sqrt_price_current_x64, sqrt_price_upper_x64, liquidity, round_up)?,
+            get_amount_b_delta(sqrt_price_lower_x64, sqrt_price_current_x64, liquidity, round_up)?,
+        )
+    };
+
+    Ok((
+        amount_a.try_into().map_err(|_| CLMMError::ArithmeticOverflow)?,
+        amount_b.try_into().map_err(|_| CLMMError::ArithmeticOverflow)?,
+    ))
+}
 
+/// Most liquidity `[lower, upper]` can hold at the current price without
+/// needing more than `amount_a` of token A or `amount_b` of token B.
+pub fn get_liquidity_for_amounts(
+    sqrt_price_current_x64: u128,
+    sqrt_price_lower_x64: u128,
+    sqrt_price_upper_x64: u128,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<u128> {
     if sqrt_price_current_x64 <= sqrt_price_lower_x64 {
-        // Token A only: amount_a = L * (upper - lower) * Q64 / (upper * lower)
-        let numerator = liquidity
-            .checked_mul(
-                sqrt_price_upper_x64
-                    .checked_sub(sqrt_price_lower_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        let denominator = sqrt_price_upper_x64
-            .checked_mul(sqrt_price_lower_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        amount_a = (numerator / denominator)
-            .try_into()
-            .map_err(|_| CLMMError::ArithmeticOverflow)?;
-        amount_b = 0;
+        get_liquidity_for_amount_a(sqrt_price_lower_x64, sqrt_price_upper_x64, amount_a)
     } else if sqrt_price_current_x64 >= sqrt_price_upper_x64 {
-        // Token B only: amount_b = L * (upper - lower) / Q64
-        amount_a = 0;
-        amount_b = (liquidity
-            .checked_mul(
-                sqrt_price_upper_x64
-                    .checked_sub(sqrt_price_lower_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?)
-        .try_into()
-        .map_err(|_| CLMMError::ArithmeticOverflow)?;
+        get_liquidity_for_amount_b(sqrt_price_lower_x64, sqrt_price_upper_x64, amount_b)
+    } else {
+        let liquidity_a = get_liquidity_for_amount_a(sqrt_price_current_x64, sqrt_price_upper_x64, amount_a)?;
+        let liquidity_b = get_liquidity_for_amount_b(sqrt_price_lower_x64, sqrt_price_current_x64, amount_b)?;
+        Ok(liquidity_a.min(liquidity_b))
+    }
+}
+
+/// Liquidity `amount_a` of token A buys between two sqrt prices:
+/// amount_a * upper * lower / ((upper - lower) * Q64)
+pub fn get_liquidity_for_amount_a(sqrt_price_a_x64: u128, sqrt_price_b_x64: u128, amount_a: u64) -> Result<u128> {
+    let (lower, upper) = if sqrt_price_a_x64 < sqrt_price_b_x64 {
+        (sqrt_price_a_x64, sqrt_price_b_x64)
     } else {
-        // Both tokens:
-        // amount_a = L * (upper - current) * Q64 / (upper * current)
-        let numerator_a = liquidity
-            .checked_mul(
-                sqrt_price_upper_x64
-                    .checked_sub(sqrt_price_current_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        let denominator_a = sqrt_price_upper_x64
-            .checked_mul(sqrt_price_current_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        amount_a = (numerator_a / denominator_a)
-            .try_into()
-            .map_err(|_| CLMMError::ArithmeticOverflow)?;
-
-        // amount_b = L * (current - lower) / Q64
-        amount_b = (liquidity
-            .checked_mul(
-                sqrt_price_current_x64
-                    .checked_sub(sqrt_price_lower_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?)
-        .try_into()
-        .map_err(|_| CLMMError::ArithmeticOverflow)?;
-    }
-
-    Ok((amount_a, amount_b))
+        (sqrt_price_b_x64, sqrt_price_a_x64)
+    };
+    U256::from(amount_a)
+        .mul_div_floor(U256::from(upper) * U256::from(lower), U256::from(upper - lower) << 64)
+        .and_then(U256::as_u128_checked)
+        .ok_or(CLMMError::ArithmeticOverflow.into())
 }
 
+/// Liquidity `amount_b` of token B buys between two sqrt prices:
+/// amount_b * Q64 / (upper - lower)
+pub fn get_liquidity_for_amount_b(sqrt_price_a_x64: u128, sqrt_price_b_x64: u128, amount_b: u64) -> Result<u128> {
+    mul_div_floor(amount_b as u128, Q64, sqrt_price_a_x64.abs_diff(sqrt_price_b_x64))
+}
+
+/// Token A needed to move `liquidity` between two sqrt prices:
+/// L * (upper - lower) * Q64 / (upper * lower)
+pub fn get_amount_a_delta(
+    sqrt_price_a_x64: u128,
+    sqrt_price_b_x64: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128> {
+    let (lower, upper) = if sqrt_price_a_x64 < sqrt_price_b_x64 {
+        (sqrt_price_a_x64, sqrt_price_b_x64)
+    } else {
+        (sqrt_price_b_x64, sqrt_price_a_x64)
+    };
+    require!(lower > 0, CLMMError::SqrtPriceX64);
+    let numerator = U256::from(liquidity) << 64;
+    let (price_diff, upper, lower) = (U256::from(upper - lower), U256::from(upper), U256::from(lower));
+    if round_up {
+        numerator
+            .mul_div_ceil(price_diff, upper)
+            .and_then(|amount| amount.mul_div_ceil(U256::one(), lower))
+    } else {
+        numerator
+            .mul_div_floor(price_diff, upper)
+            .map(|amount| amount / lower)
+    }
+    .and_then(U256::as_u128_checked)
+    .ok_or(CLMMError::ArithmeticOverflow.into())
+}
+
+/// Token B needed to move `liquidity` between two sqrt prices:
+/// L * (upper - lower) / Q64
+pub fn get_amount_b_delta(
+    sqrt_price_a_x64: u128,
+    sqrt_price_b_x64: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128> {
+    let price_diff = sqrt_price_a_x64.abs_diff(sqrt_price_b_x64);
+    if round_up {
+        mul_div_ceil(liquidity, price_diff, Q64)
+    } else {
+        mul_div_floor(liquidity, price_diff, Q64)
+    }
+}
+
+/// Sqrt price after `amount` of token A is added to (price goes down) or
+/// removed from (price goes up) the pool:
+/// L * Q64 * sqrt_p / (L * Q64 +- amount * sqrt_p)
+/// Rounded up either way, so the price never moves further than paid for.
+fn next_sqrt_price_from_amount_a(sqrt_price_x64: u128, liquidity: u128, amount: u128, add: bool) -> Result<u128> {
+    if amount == 0 {
+        return Ok(sqrt_price_x64);
+    }
+    let numerator = U256::from(liquidity) << 64;
+    let product = U256::from(amount) * U256::from(sqrt_price_x64);
+    let denominator = if add {
+        numerator.checked_add(product)
+    } else {
+        numerator.checked_sub(product)
+    }
+    .ok_or(CLMMError::ArithmeticOverflow)?;
+    numerator
+        .mul_div_ceil(U256::from(sqrt_price_x64), denominator)
+        .and_then(U256::as_u128_checked)
+        .ok_or(CLMMError::ArithmeticOverflow.into())
+}
+
+/// Sqrt price after `amount` of token B is added to (price goes up) or
+/// removed from (price goes down) the pool: sqrt_p +- amount * Q64 / L
+/// Rounded down either way, so the price never moves further than paid for.
+fn next_sqrt_price_from_amount_b(sqrt_price_x64: u128, liquidity: u128, amount: u128, add: bool) -> Result<u128> {
+    if add {
+        sqrt_price_x64.checked_add(mul_div_floor(amount, Q64, liquidity)?)
+    } else {
+        sqrt_price_x64.checked_sub(mul_div_ceil(amount, Q64, liquidity)?)
+    }
+    .ok_or(CLMMError::ArithmeticOverflow.into())
+}
+
+/// Sqrt price after `amount_in` is paid into the pool in the swap direction
+pub fn get_next_sqrt_price_from_input(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount_in: u128,
+    a_to_b: bool,
+) -> Result<u128> {
+    if a_to_b {
+        next_sqrt_price_from_amount_a(sqrt_price_x64, liquidity, amount_in, true)
+    } else {
+        next_sqrt_price_from_amount_b(sqrt_price_x64, liquidity, amount_in, true)
+    }
+}
+
+/// Sqrt price after `amount_out` is paid out of the pool in the swap direction
+pub fn get_next_sqrt_price_from_output(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount_out: u128,
+    a_to_b: bool,
+) -> Result<u128> {
+    if a_to_b {
+        next_sqrt_price_from_amount_b(sqrt_price_x64, liquidity, amount_out, false)
+    } else {
+        next_sqrt_price_from_amount_a(sqrt_price_x64, liquidity, amount_out, false)
+    }
+}
+
+/// Returns (next sqrt price, amount in, amount out, fee amount).
+/// `amount_remaining` is the input left to spend when `amount_specified_is_input`,
+/// and the fee is taken out of it before it is applied to the price. Otherwise
+/// it is the output still owed, and the fee is charged on top of the input.
 pub fn compute_swap_step(
     sqrt_price_current_x64: u128,
     sqrt_price_target_x64: u128,
     liquidity: u128,
     amount_remaining: u128,
+    fee_rate: u32,
+    amount_specified_is_input: bool,
     a_to_b: bool,
-) -> Result<(u128, u128, u128)> {
-    let next_price: u128;
-    let amount_in: u128;
-    let amount_out: u128;
-
-    if a_to_b {
-        // Calculate required input for full step
-        let price_diff = sqrt_price_current_x64
-            .checked_sub(sqrt_price_target_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        let required_in = liquidity
-            .checked_mul(price_diff)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(
-                sqrt_price_current_x64
-                    .checked_mul(sqrt_price_target_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        if amount_remaining >= required_in {
-            // Full step
-            next_price = sqrt_price_target_x64;
-            amount_in = required_in;
+) -> Result<(u128, u128, u128, u128)> {
+    // Input is rounded up and output down, so every step leaves any
+    // remainder with the pool
+    let amount_remaining_less_fee = mul_div_floor(
+        amount_remaining,
+        (FEE_RATE_DENOMINATOR - fee_rate) as u128,
+        FEE_RATE_DENOMINATOR as u128,
+    )?;
+    let next_price = if amount_specified_is_input {
+        // Input a full step to the target would need
+        let required_in = if a_to_b {
+            get_amount_a_delta(sqrt_price_target_x64, sqrt_price_current_x64, liquidity, true)?
+        } else {
+            get_amount_b_delta(sqrt_price_current_x64, sqrt_price_target_x64, liquidity, true)?
+        };
+        if amount_remaining_less_fee >= required_in {
+            sqrt_price_target_x64
         } else {
-            // Partial step
-            let numerator = liquidity
-                .checked_mul(sqrt_price_current_x64)
-                .ok_or(CLMMError::ArithmeticOverflow)?
-                .checked_mul(sqrt_price_current_x64)
-                .ok_or(CLMMError::ArithmeticOverflow)?;
-
-            let denominator = liquidity
-                .checked_mul(sqrt_price_current_x64)
-                .ok_or(CLMMError::ArithmeticOverflow)?
-                .checked_add(
-                    amount_remaining
-                        .checked_mul(sqrt_price_current_x64)
-                        .ok_or(CLMMError::ArithmeticOverflow)?
-                        .checked_div(Q64)
-                        .ok_or(CLMMError::ArithmeticOverflow)?,
-                )
-                .ok_or(CLMMError::ArithmeticOverflow)?;
-
-            next_price = numerator
-                .checked_div(denominator)
-                .ok_or(CLMMError::ArithmeticOverflow)?;
-            amount_in = amount_remaining;
+            get_next_sqrt_price_from_input(sqrt_price_current_x64, liquidity, amount_remaining_less_fee, a_to_b)?
         }
-
-        // Calculate output
-        amount_out = liquidity
-            .checked_mul(
-                sqrt_price_current_x64
-                    .checked_sub(next_price)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
     } else {
-        // B to A swap
-        let price_diff = sqrt_price_target_x64
-            .checked_sub(sqrt_price_current_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        let required_in = liquidity
-            .checked_mul(price_diff)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        if amount_remaining >= required_in {
-            // Full step
-            next_price = sqrt_price_target_x64;
-            amount_in = required_in;
+        // Output a full step to the target would pay
+        let required_out = if a_to_b {
+            get_amount_b_delta(sqrt_price_target_x64, sqrt_price_current_x64, liquidity, false)?
+        } else {
+            get_amount_a_delta(sqrt_price_current_x64, sqrt_price_target_x64, liquidity, false)?
+        };
+        if amount_remaining >= required_out {
+            sqrt_price_target_x64
         } else {
-            // Partial step
-            next_price = sqrt_price_current_x64
-                .checked_add(
-                    amount_remaining
-                        .checked_mul(Q64)
-                        .ok_or(CLMMError::ArithmeticOverflow)?
-                        .checked_div(liquidity)
-                        .ok_or(CLMMError::ArithmeticOverflow)?,
-                )
-                .ok_or(CLMMError::ArithmeticOverflow)?;
-            amount_in = amount_remaining;
+            get_next_sqrt_price_from_output(sqrt_price_current_x64, liquidity, amount_remaining, a_to_b)?
         }
+    };
+    let full_step = next_price == sqrt_price_target_x64;
+
+    let amount_in = if amount_specified_is_input && !full_step {
+        // A partial exact-input step spends everything left after the fee
+        amount_remaining_less_fee
+    } else if a_to_b {
+        get_amount_a_delta(next_price, sqrt_price_current_x64, liquidity, true)?
+    } else {
+        get_amount_b_delta(sqrt_price_current_x64, next_price, liquidity, true)?
+    };
+    let mut amount_out = if a_to_b {
+        get_amount_b_delta(next_price, sqrt_price_current_x64, liquidity, false)?
+    } else {
+        get_amount_a_delta(sqrt_price_current_x64, next_price, liquidity, false)?
+    };
+
+    // Never pay out more than was asked for
+    if !amount_specified_is_input && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    // A partial exact-input step consumes everything that is left, so the
+    // rest is fee. Otherwise the fee is charged on the input the step needed,
+    // rounded up.
+    let fee_amount = if amount_specified_is_input && !full_step {
+        amount_remaining - amount_in
+    } else {
+        mul_div_ceil(amount_in, fee_rate as u128, (FEE_RATE_DENOMINATOR - fee_rate) as u128)?
+    };
+
+    Ok((next_price, amount_in, amount_out, fee_amount))
+}
+
+/// Start index of the tick array holding `tick`, rounding towards negative infinity
+pub fn tick_array_start_index(tick: i32, tick_spacing: i32) -> i32 {
+    let ticks_in_array = TICK_ARRAY_SIZE * tick_spacing;
+    tick.div_euclid(ticks_in_array) * ticks_in_array
+}
+
+// position of a tick array in the pool bitmap and its extension laid end to end:
+// the extension's negative half, the pool's 16 words, then the positive half
+fn tick_array_bit(start_tick_index: i32, tick_spacing: i32) -> i32 {
+    start_tick_index.div_euclid(TICK_ARRAY_SIZE * tick_spacing) + TICK_ARRAY_BITMAP_TOTAL_HALF
+}
+
+fn bitmap_word(bitmap: &[u64; 16], extension: &TickArrayBitmapExtension, word: usize) -> u64 {
+    match word.checked_sub(TICK_ARRAY_BITMAP_EXTENSION_WORDS) {
+        None => extension.negative_bitmap[word],
+        Some(word) if word < 16 => bitmap[word],
+        Some(word) => extension.positive_bitmap[word - 16],
+    }
+}
+
+fn bitmap_word_mut<'a>(bitmap: &'a mut [u64; 16], extension: &'a mut TickArrayBitmapExtension, word: usize) -> &'a mut u64 {
+    match word.checked_sub(TICK_ARRAY_BITMAP_EXTENSION_WORDS) {
+        None => &mut extension.negative_bitmap[word],
+        Some(word) if word < 16 => &mut bitmap[word],
+        Some(word) => &mut extension.positive_bitmap[word - 16],
+    }
+}
+
+pub fn check_tick_array_start_index(start_tick_index: i32, tick_spacing: i32) -> Result<()> {
+    require!(
+        start_tick_index == tick_array_start_index(start_tick_index, tick_spacing),
+        CLMMError::InvalidTickArray
+    );
+    let bit = tick_array_bit(start_tick_index, tick_spacing);
+    require!(
+        (0..2 * TICK_ARRAY_BITMAP_TOTAL_HALF).contains(&bit),
+        CLMMError::TickArrayOutOfRange
+    );
+    Ok(())
+}
+
+pub fn is_tick_array_initialized(
+    bitmap: &[u64; 16],
+    extension: &TickArrayBitmapExtension,
+    start_tick_index: i32,
+    tick_spacing: i32,
+) -> bool {
+    let bit = tick_array_bit(start_tick_index, tick_spacing);
+    (0..2 * TICK_ARRAY_BITMAP_TOTAL_HALF).contains(&bit)
+        && bitmap_word(bitmap, extension, (bit / 64) as usize) & (1u64 << (bit % 64)) != 0
+}
+
+pub fn flip_tick_array_bit(
+    bitmap: &mut [u64; 16],
+    extension: &mut TickArrayBitmapExtension,
+    start_tick_index: i32,
+    tick_spacing: i32,
+) -> Result<()> {
+    check_tick_array_start_index(start_tick_index, tick_spacing)?;
+    let bit = tick_array_bit(start_tick_index, tick_spacing);
+    *bitmap_word_mut(bitmap, extension, (bit / 64) as usize) ^= 1u64 << (bit % 64);
+    Ok(())
+}
+
+/// Start index of the next tick array with initialized ticks strictly past
+/// `start_tick_index` in the swap direction, if any.
+pub fn next_initialized_tick_array(
+    bitmap: &[u64; 16],
+    extension: &TickArrayBitmapExtension,
+    start_tick_index: i32,
+    tick_spacing: i32,
+    a_to_b: bool,
+) -> Option<i32> {
+    let bit = tick_array_bit(start_tick_index, tick_spacing);
+    // scan a word at a time, masking off the bits at or behind `bit` in the first one
+    let found = if a_to_b {
+        let last = bit.min(2 * TICK_ARRAY_BITMAP_TOTAL_HALF) - 1;
+        if last < 0 {
+            return None;
+        }
+        let mut word = (last / 64) as usize;
+        let mut bits = bitmap_word(bitmap, extension, word) & (u64::MAX >> (63 - last % 64));
+        while bits == 0 {
+            word = word.checked_sub(1)?;
+            bits = bitmap_word(bitmap, extension, word);
+        }
+        word as i32 * 64 + 63 - bits.leading_zeros() as i32
+    } else {
+        let first = bit.max(-1) + 1;
+        if first >= 2 * TICK_ARRAY_BITMAP_TOTAL_HALF {
+            return None;
+        }
+        let mut word = (first / 64) as usize;
+        let mut bits = bitmap_word(bitmap, extension, word) & (u64::MAX << (first % 64));
+        while bits == 0 {
+            word += 1;
+            if word == TICK_ARRAY_BITMAP_WORDS {
+                return None;
+            }
+            bits = bitmap_word(bitmap, extension, word);
+        }
+        word as i32 * 64 + bits.trailing_zeros() as i32
+    };
+    Some((found - TICK_ARRAY_BITMAP_TOTAL_HALF) * TICK_ARRAY_SIZE * tick_spacing)
+}
+
+/// First tick array a swap starting at `current_tick` has to load: the array
+/// holding the current tick if it has initialized ticks, otherwise the next one.
+pub fn first_initialized_tick_array(
+    bitmap: &[u64; 16],
+    extension: &TickArrayBitmapExtension,
+    current_tick: i32,
+    tick_spacing: i32,
+    a_to_b: bool,
+) -> Option<i32> {
+    let start = tick_array_start_index(current_tick, tick_spacing);
+    if is_tick_array_initialized(bitmap, extension, start, tick_spacing) {
+        Some(start)
+    } else {
+        next_initialized_tick_array(bitmap, extension, start, tick_spacing, a_to_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices() -> (u128, u128, u128) {
+        (
+            tick_to_sqrt_price_x64(-100).unwrap(),
+            tick_to_sqrt_price_x64(0).unwrap(),
+            tick_to_sqrt_price_x64(100).unwrap(),
+        )
+    }
+
+    #[test]
+    fn deposits_round_up_and_withdrawals_round_down() {
+        let (lower, current, upper) = prices();
+        for current in [lower - 1, current, upper + 1] {
+            let deposit = calculate_liquidity_amounts(current, lower, upper, 1_000_001, true).unwrap();
+            let withdrawal = calculate_liquidity_amounts(current, lower, upper, 1_000_001, false).unwrap();
+            let exact_a = get_amount_a_delta(current.max(lower).min(upper), upper, 1_000_001, false).unwrap();
+            let exact_b = get_amount_b_delta(lower, current.max(lower).min(upper), 1_000_001, false).unwrap();
+            assert_eq!(withdrawal, (exact_a as u64, exact_b as u64));
+            // every inexact side costs one more to put in than it pays to take out
+            assert!(deposit.0 >= withdrawal.0 && deposit.0 - withdrawal.0 <= 1);
+            assert!(deposit.1 >= withdrawal.1 && deposit.1 - withdrawal.1 <= 1);
+            assert!(deposit.0 + deposit.1 > withdrawal.0 + withdrawal.1);
+        }
+    }
 
-        // Calculate output
-        let price_diff_out = next_price
-            .checked_sub(sqrt_price_current_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-        amount_out = liquidity
-            .checked_mul(price_diff_out)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(
-                sqrt_price_current_x64
-                    .checked_mul(next_price)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-    }
-
-    Ok((next_price, amount_in, amount_out))
-}
\ No newline at end of file
+    #[test]
+    fn dust_liquidity_is_never_free() {
+        let (lower, current, upper) = prices();
+        assert_eq!(calculate_liquidity_amounts(current, lower, upper, 1, true).unwrap(), (1, 1));
+        assert_eq!(calculate_liquidity_amounts(current, lower, upper, 1, false).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn liquidity_for_amounts_never_needs_more_than_offered() {
+        let (lower, current, upper) = prices();
+        for (amount_a, amount_b) in [(1_000, 1_000), (999_999, 12_345), (7, 1_000_000_000)] {
+            let liquidity = get_liquidity_for_amounts(current, lower, upper, amount_a, amount_b).unwrap();
+            let (needed_a, needed_b) = calculate_liquidity_amounts(current, lower, upper, liquidity, true).unwrap();
+            assert!(needed_a <= amount_a && needed_b <= amount_b);
+        }
+    }
+
+    #[test]
+    fn partial_exact_input_step_rounds_for_the_pool() {
+        let (lower, current, _) = prices();
+        let liquidity = 1_000_000_007;
+        let (next, amount_in, amount_out, fee) =
+            compute_swap_step(current, lower, liquidity, 10_000, 3_000, true, true).unwrap();
+        assert!(next > lower && next < current);
+        // all of the input is spent, and the fee is whatever the price move did not need
+        assert_eq!(amount_in + fee, 10_000);
+        assert!(amount_in >= get_amount_a_delta(next, current, liquidity, false).unwrap());
+        assert_eq!(amount_out, get_amount_b_delta(next, current, liquidity, false).unwrap());
+        assert!(amount_out < get_amount_b_delta(next, current, liquidity, true).unwrap());
+    }
+
+    #[test]
+    fn full_step_pays_in_rounded_up_and_out_rounded_down() {
+        let (_, current, upper) = prices();
+        let liquidity = 1_000_000_007;
+        let (next, amount_in, amount_out, fee) =
+            compute_swap_step(current, upper, liquidity, u64::MAX as u128, 3_000, true, false).unwrap();
+        assert_eq!(next, upper);
+        assert_eq!(amount_in, get_amount_b_delta(current, upper, liquidity, true).unwrap());
+        assert_eq!(amount_out, get_amount_a_delta(current, upper, liquidity, false).unwrap());
+        assert_eq!(fee, mul_div_ceil(amount_in, 3_000, 997_000).unwrap());
+    }
+
+    #[test]
+    fn exact_output_step_charges_rounded_up_input() {
+        let (_, current, upper) = prices();
+        let liquidity = 1_000_000_007;
+        let (next, amount_in, amount_out, fee) =
+            compute_swap_step(current, upper, liquidity, 10_000, 3_000, false, false).unwrap();
+        assert!(next > current && next < upper);
+        assert_eq!(amount_out, 10_000);
+        assert_eq!(amount_in, get_amount_b_delta(current, next, liquidity, true).unwrap());
+        assert!(amount_in > get_amount_b_delta(current, next, liquidity, false).unwrap());
+        assert!(fee > 0);
+    }
+
+    #[test]
+    fn swapping_there_and_back_without_fees_cannot_profit() {
+        let (lower, current, upper) = prices();
+        let liquidity = 1_000_000_007;
+        for amount in [1, 999, 123_456] {
+            let (price, _, out_b, _) = compute_swap_step(current, lower, liquidity, amount, 0, true, true).unwrap();
+            let (_, _, out_a, _) = compute_swap_step(price, upper, liquidity, out_b, 0, true, false).unwrap();
+            assert!(out_a <= amount);
+        }
+    }
+
+    #[test]
+    fn tick_array_bitmap_reaches_both_ends_at_spacing_one() {
+        let mut bitmap = [0u64; 16];
+        let mut extension = TickArrayBitmapExtension {
+            pool: Pubkey::default(),
+            negative_bitmap: [0; TICK_ARRAY_BITMAP_EXTENSION_WORDS],
+            positive_bitmap: [0; TICK_ARRAY_BITMAP_EXTENSION_WORDS],
+        };
+        let lowest = tick_array_start_index(MIN_TICK, 1);
+        let highest = tick_array_start_index(MAX_TICK, 1);
+        for start in [lowest, -600, 0, highest] {
+            flip_tick_array_bit(&mut bitmap, &mut extension, start, 1).unwrap();
+        }
+        assert!(is_tick_array_initialized(&bitmap, &extension, lowest, 1));
+        assert_eq!(next_initialized_tick_array(&bitmap, &extension, 0, 1, true), Some(-600));
+        assert_eq!(next_initialized_tick_array(&bitmap, &extension, -600, 1, true), Some(lowest));
+        assert_eq!(next_initialized_tick_array(&bitmap, &extension, lowest, 1, true), None);
+        assert_eq!(next_initialized_tick_array(&bitmap, &extension, 0, 1, false), Some(highest));
+        assert_eq!(next_initialized_tick_array(&bitmap, &extension, highest, 1, false), None);
+        assert_eq!(first_initialized_tick_array(&bitmap, &extension, 5, 1, true), Some(0));
+    }
+}
@@ -1,10 +1,25 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Clmm } from "../target/types/clmm";
-import { PublicKey, Keypair, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
-import { TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID, createMint, createAccount, mintTo, getAccount } from "@solana/spl-token";
+import { PublicKey, Keypair, SystemProgram, Transaction, sendAndConfirmTransaction } from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
+  ExtensionType,
+  getMintLen,
+  createInitializeNonTransferableMintInstruction,
+  createInitializeMintInstruction,
+  createWrappedNativeAccount,
+  createInitializeTransferFeeConfigInstruction,
+  NATIVE_MINT,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createMint,
+  createAssociatedTokenAccount,
+  getAssociatedTokenAddressSync,
+  mintTo,
+  getAccount,
+} from "@solana/spl-token";
 import { expect } from "chai";
-import { SYSTEM_PROGRAM_ID } from "@coral-xyz/anchor/dist/cjs/native/system";
 
 describe("clmm", () => {
   // Configure the client to use the local cluster.
@@ -15,390 +30,2362 @@ describe("clmm", () => {
 
   // Test accounts
   let user: Keypair;
+  let treasury: Keypair;
+  let ammConfig: PublicKey;
+  let programData: PublicKey;
   let mintA: PublicKey;
   let mintB: PublicKey;
   let userTokenAccountA: PublicKey;
   let userTokenAccountB: PublicKey;
   let pool: PublicKey;
-  let lpMint: PublicKey;
+  let observation: PublicKey;
+  let tickArrayBitmap: PublicKey;
   let vaultA: PublicKey;
   let vaultB: PublicKey;
-  let userLpAccount: PublicKey;
-  let tickLower: PublicKey;
-  let tickUpper: PublicKey;
+  let tickArrayLower: PublicKey;
+  let tickArrayUpper: PublicKey;
+  let positionMint: Keypair;
+  let position: PublicKey;
+  let positionTokenAccount: PublicKey;
+  let rewardMint: PublicKey;
+  let rewardVault: PublicKey;
+  let eventAuthority: PublicKey;
+  // A second pool pairs token A with native SOL
+  let nativeMintA: PublicKey;
+  let nativeMintB: PublicKey;
+  let nativePool: PublicKey;
+  let nativeVaultA: PublicKey;
+  let nativeVaultB: PublicKey;
+  let nativePositionMint: Keypair;
+  let nativePosition: PublicKey;
+  let userWsolAccount: PublicKey;
+  let wsolAccount: PublicKey;
+  // A third pool pairs token A with a Token-2022 mint charging a transfer fee
+  let feeMint: PublicKey;
+  let feeMintA: PublicKey;
+  let feeMintB: PublicKey;
+  let feeTokenProgramA: PublicKey;
+  let feeTokenProgramB: PublicKey;
+  let feePool: PublicKey;
+  let feeVaultA: PublicKey;
+  let feeVaultB: PublicKey;
+  let feePositionMint: Keypair;
+  let feePosition: PublicKey;
 
-  const TICK_SPACING = 1;
-  const INITIAL_PRICE = 1000000; // 1.0 in price units
-  const SEED = 12345;
+  // 1 token B per token A, both mints have 6 decimals
+  const INITIAL_PRICE = { ratio: { numerator: new anchor.BN(1), denominator: new anchor.BN(1) } };
+  const FEE_RATE = 500; // 0.05%, tick spacing 10
+  const TICK_LOWER = -100;
+  const TICK_UPPER = 100;
+  const FEE_TIERS = [
+    { feeRate: 100, tickSpacing: 1, protocolFeeRate: 120000 },
+    { feeRate: 500, tickSpacing: 10, protocolFeeRate: 120000 },
+    { feeRate: 3000, tickSpacing: 60, protocolFeeRate: 120000 },
+    { feeRate: 10000, tickSpacing: 200, protocolFeeRate: 120000 },
+  ];
+
+  // One pool per config, mint pair and fee tier
+  const poolAddress = (minta: PublicKey, mintb: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool"),
+        ammConfig.toBuffer(),
+        minta.toBuffer(),
+        mintb.toBuffer(),
+        new anchor.BN(FEE_RATE).toArrayLike(Buffer, "le", 4),
+      ],
+      program.programId
+    )[0];
+
+  const observationAddress = (pool: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("observation"), pool.toBuffer()], program.programId)[0];
+
+  const tickArrayBitmapAddress = (pool: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("tick_array_bitmap"), pool.toBuffer()], program.programId)[0];
+
+  const tickArrayAddress = (startTickIndex: number, forPool: PublicKey = pool) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("tick_array"),
+        forPool.toBuffer(),
+        new anchor.BN(startTickIndex).toTwos(32).toArrayLike(Buffer, "le", 4),
+      ],
+      program.programId
+    )[0];
 
   before(async () => {
     // Create test user
     user = Keypair.generate();
-    
+    treasury = Keypair.generate();
+
     // Airdrop SOL to user
     const signature = await provider.connection.requestAirdrop(user.publicKey, 10 * anchor.web3.LAMPORTS_PER_SOL);
     await provider.connection.confirmTransaction(signature);
 
     // Create test tokens
-    mintA = await createMint(
-      provider.connection,
-      user,
-      user.publicKey,
-      user.publicKey,
-      6
-    );
-
-    mintB = await createMint(
-      provider.connection,
-      user,
-      user.publicKey,
-      user.publicKey,
-      6
-    );
+    mintA = await createMint(provider.connection, user, user.publicKey, user.publicKey, 6);
+    mintB = await createMint(provider.connection, user, user.publicKey, user.publicKey, 6);
+    // Pools take their mints in sorted order
+    if (Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) > 0) {
+      [mintA, mintB] = [mintB, mintA];
+    }
 
     // Create user token accounts
-    userTokenAccountA = await createAccount(
-      provider.connection,
-      user,
-      mintA,
-      user.publicKey
-    );
-
-    userTokenAccountB = await createAccount(
-      provider.connection,
-      user,
-      mintB,
-      user.publicKey
-    );
+    userTokenAccountA = await createAssociatedTokenAccount(provider.connection, user, mintA, user.publicKey);
+    userTokenAccountB = await createAssociatedTokenAccount(provider.connection, user, mintB, user.publicKey);
 
     // Mint some tokens to user
-    await mintTo(
-      provider.connection,
-      user,
-      mintA,
-      userTokenAccountA,
-      user,
-      1000000000 // 1000 tokens
-    );
+    await mintTo(provider.connection, user, mintA, userTokenAccountA, user, 1000000000); // 1000 tokens
+    await mintTo(provider.connection, user, mintB, userTokenAccountB, user, 1000000000); // 1000 tokens
 
-    await mintTo(
-      provider.connection,
-      user,
-      mintB,
-      userTokenAccountB,
-      user,
-      1000000000 // 1000 tokens
+    // The config is a singleton created by the program's upgrade authority
+    [ammConfig] = PublicKey.findProgramAddressSync([Buffer.from("amm_config")], program.programId);
+    [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
     );
 
+    // Events are emitted through a self-CPI signed by this PDA
+    [eventAuthority] = PublicKey.findProgramAddressSync([Buffer.from("__event_authority")], program.programId);
+
     // Derive pool and related accounts
-    const [poolKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from("config"), new anchor.BN(SEED).toArrayLike(Buffer, "le", 8)],
-      program.programId
-    );
-    pool = poolKey;
+    pool = poolAddress(mintA, mintB);
+    observation = observationAddress(pool);
+    tickArrayBitmap = tickArrayBitmapAddress(pool);
 
-    const [lpMintKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from("lp"), pool.toBuffer()],
-      program.programId
-    );
-    lpMint = lpMintKey;
+    // Vaults are the pool's associated token accounts
+    vaultA = getAssociatedTokenAddressSync(mintA, pool, true);
+    vaultB = getAssociatedTokenAddressSync(mintB, pool, true);
 
-    const [vaultAKey] = PublicKey.findProgramAddressSync(
-      [pool.toBuffer(), mintA.toBuffer(), Buffer.from("vault")],
+    // Derive the tick arrays holding ticks -100 and 100 (60 ticks of spacing 10 per array)
+    tickArrayLower = tickArrayAddress(-600);
+    tickArrayUpper = tickArrayAddress(0);
+
+    // Position accounts are keyed by a fresh one-of-one mint
+    positionMint = Keypair.generate();
+    [position] = PublicKey.findProgramAddressSync(
+      [Buffer.from("position"), positionMint.publicKey.toBuffer()],
       program.programId
     );
-    vaultA = vaultAKey;
+    positionTokenAccount = getAssociatedTokenAddressSync(positionMint.publicKey, user.publicKey);
 
-    const [vaultBKey] = PublicKey.findProgramAddressSync(
-      [pool.toBuffer(), mintB.toBuffer(), Buffer.from("vault")],
+    // Reward vaults are pool-owned PDAs per reward mint
+    rewardMint = await createMint(provider.connection, user, user.publicKey, user.publicKey, 6);
+    [rewardVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("reward_vault"), pool.toBuffer(), rewardMint.toBuffer()],
       program.programId
     );
-    vaultB = vaultBKey;
+  });
 
-    const [userLpAccountKey] = PublicKey.findProgramAddressSync(
-      [user.publicKey.toBuffer(), lpMint.toBuffer()],
-      ASSOCIATED_TOKEN_PROGRAM_ID
-    );
-    userLpAccount = userLpAccountKey;
+  it("Create config", async () => {
+    try {
+      const tx = await program.methods
+        .createConfig(treasury.publicKey, FEE_TIERS)
+        .accountsStrict({
+          signer: provider.publicKey,
+          ammConfig: ammConfig,
+          program: program.programId,
+          programData: programData,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
 
-    // Derive tick accounts
-    const [tickLowerKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from("tick"), pool.toBuffer(), new anchor.BN(-100).toArrayLike(Buffer, "le", 4)],
-      program.programId
-    );
-    tickLower = tickLowerKey;
+      console.log("Config created with signature:", tx);
 
-    const [tickUpperKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from("tick"), pool.toBuffer(), new anchor.BN(100).toArrayLike(Buffer, "le", 4)],
-      program.programId
-    );
-    tickUpper = tickUpperKey;
+      const configAccount = await program.account.ammConfig.fetch(ammConfig);
+      expect(configAccount.admin.toString()).to.equal(provider.publicKey.toString());
+      expect(configAccount.treasury.toString()).to.equal(treasury.publicKey.toString());
+      expect(configAccount.feeTiers.length).to.equal(FEE_TIERS.length);
+    } catch (error) {
+      console.error("Error creating config:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to update config without the admin", async () => {
+    try {
+      await program.methods
+        .updateConfig(user.publicKey, null)
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+        })
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected update_config to fail");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Transfer admin", async () => {
+    try {
+      await program.methods
+        .transferAdmin(user.publicKey)
+        .accountsStrict({
+          signer: provider.publicKey,
+          ammConfig: ammConfig,
+        })
+        .rpc();
+
+      // The admin only changes once the new admin accepts
+      let configAccount = await program.account.ammConfig.fetch(ammConfig);
+      expect(configAccount.admin.toString()).to.equal(provider.publicKey.toString());
+      expect(configAccount.pendingAdmin.toString()).to.equal(user.publicKey.toString());
+
+      const tx = await program.methods
+        .acceptAdmin()
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Admin transferred with signature:", tx);
+
+      configAccount = await program.account.ammConfig.fetch(ammConfig);
+      expect(configAccount.admin.toString()).to.equal(user.publicKey.toString());
+      expect(configAccount.pendingAdmin.toString()).to.equal(PublicKey.default.toString());
+    } catch (error) {
+      console.error("Error transferring admin:", error);
+      throw error;
+    }
   });
 
   it("Initialize pool", async () => {
     try {
       const tx = await program.methods
-        .initPool(new anchor.BN(SEED), new anchor.BN(INITIAL_PRICE))
+        .initPool(INITIAL_PRICE, FEE_RATE)
         .accountsStrict({
           signer: user.publicKey,
+          ammConfig: ammConfig,
           minta: mintA,
           mintb: mintB,
-          lpMint: lpMint,
           vaulta: vaultA,
           config: pool,
           vaultB: vaultB,
+          observation: observation,
+          tickArrayBitmap: tickArrayBitmap,
           systemProgram: SystemProgram.programId,
-          tokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
           associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-          rent: SYSVAR_RENT_PUBKEY,
+          eventAuthority: eventAuthority,
+          program: program.programId,
         })
         .signers([user])
         .rpc();
 
       console.log("Pool initialized with signature:", tx);
-      
+
       // Verify pool was created
       const poolAccount = await program.account.pool.fetch(pool);
       expect(poolAccount.minta.toString()).to.equal(mintA.toString());
       expect(poolAccount.mintb.toString()).to.equal(mintB.toString());
-      expect(poolAccount.seed.toNumber()).to.equal(SEED);
+      expect(poolAccount.feeRate).to.equal(FEE_RATE);
+      expect(poolAccount.sqrtPrice.toString()).to.equal(new anchor.BN(1).shln(64).toString());
+      expect(poolAccount.currentTick).to.equal(0);
+      expect(poolAccount.tickSpacing).to.equal(10);
+      expect(poolAccount.ammConfig.toString()).to.equal(ammConfig.toString());
+      expect(poolAccount.protocolFeeRate).to.equal(120000);
+
+      // The oracle starts out holding a single observation
+      const observationAccount = await program.account.observationState.fetch(observation);
+      expect(observationAccount.pool.toString()).to.equal(pool.toString());
+      expect(observationAccount.observationCardinality).to.equal(1);
+      expect(observationAccount.observations[0].initialized).to.equal(1);
+
+      // Tick arrays past the pool's own bitmap are tracked by its extension
+      const bitmapExtension = await program.account.tickArrayBitmapExtension.fetch(tickArrayBitmap);
+      expect(bitmapExtension.pool.toString()).to.equal(pool.toString());
     } catch (error) {
       console.error("Error initializing pool:", error);
       throw error;
     }
   });
 
-  // it("Initialize tick accounts", async () => {
-  //   try {
-  //     // Initialize lower tick
-  //     const tx1 = await program.methods
-  //       .initTick()
-  //       .accountsStrict({
-  //         signer: user.publicKey,
-  //         config: pool,
-  //         tick: tickLower,
-  //         systemProgram: SystemProgram.programId,
-  //       })
-  //       .signers([user])
-  //       .rpc();
+  it("Increase observation cardinality", async () => {
+    try {
+      await program.methods
+        .increaseObservationCardinality(10)
+        .accountsStrict({ signer: user.publicKey, config: pool, observation: observation })
+        .signers([user])
+        .rpc();
 
-  //     console.log("Lower tick initialized with signature:", tx1);
+      // The buffer only grows once the next observation is written
+      const observationAccount = await program.account.observationState.fetch(observation);
+      expect(observationAccount.observationCardinality).to.equal(1);
+      expect(observationAccount.observationCardinalityNext).to.equal(10);
+    } catch (error) {
+      console.error("Error increasing observation cardinality:", error);
+      throw error;
+    }
+  });
 
-  //     // Initialize upper tick
-  //     const tx2 = await program.methods
-  //       .initTick()
-  //       .accountsStrict({
-  //         signer: user.publicKey,
-  //         config: pool,
-  //         tick: tickUpper,
-  //         systemProgram: SystemProgram.programId,
-  //       })
-  //       .signers([user])
-  //       .rpc();
+  it("Should fail to create a pool with unsorted mints", async () => {
+    try {
+      const reversedPool = poolAddress(mintB, mintA);
 
-  //     console.log("Upper tick initialized with signature:", tx2);
+      await program.methods
+        .initPool(INITIAL_PRICE, FEE_RATE)
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+          minta: mintB,
+          mintb: mintA,
+          vaulta: getAssociatedTokenAddressSync(mintB, reversedPool, true),
+          config: reversedPool,
+          vaultB: getAssociatedTokenAddressSync(mintA, reversedPool, true),
+          observation: observationAddress(reversedPool),
+          tickArrayBitmap: tickArrayBitmapAddress(reversedPool),
+          systemProgram: SystemProgram.programId,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
 
-  //     // Verify ticks were created
-  //     const lowerTickAccount = await program.account.tick.fetch(tickLower);
-  //     const upperTickAccount = await program.account.tick.fetch(tickUpper);
-      
-  //     expect(lowerTickAccount.index).to.equal(-100);
-  //     expect(upperTickAccount.index).to.equal(100);
-  //   } catch (error) {
-  //     console.error("Error initializing ticks:", error);
-  //     throw error;
-  //   }
-  // });
+      expect.fail("Expected init_pool to fail");
+    } catch (error) {
+      expect(error.message).to.include("InvalidMintOrder");
+    }
+  });
 
-  // it("Add liquidity", async () => {
-  //   try {
-  //     const liquidity = new anchor.BN(1000000); // 1M liquidity units
-  //     const tickLowerIndex = -100;
-  //     const tickUpperIndex = 100;
-
-  //     const tx = await program.methods
-  //       .addLiquidity(
-  //         tickLowerIndex,
-  //         tickUpperIndex,
-  //         liquidity
-  //       )
-  //       .accountsStrict({
-  //         signer: user.publicKey,
-  //         minta: mintA,
-  //         mintb: mintB,
-  //         lpMint: lpMint,
-  //         usertokenAccountA: userTokenAccountA,
-  //         usertokenAccountB: userTokenAccountB,
-  //         userLpAccount: userLpAccount,
-  //         vaulta: vaultA,
-  //         config: pool,
-  //         vaultB: vaultB,
-  //         uppertick: tickUpper,
-  //         lowertick: tickLower,
-  //         systemProgram: SystemProgram.programId,
-  //         tokenProgram: TOKEN_PROGRAM_ID,
-  //         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-  //       })
-  //       .signers([user])
-  //       .rpc();
+  it("Should fail to create a pool with a non-transferable mint", async () => {
+    try {
+      // Token-2022 mint whose tokens could never leave the vault
+      const frozenMint = Keypair.generate();
+      const mintLen = getMintLen([ExtensionType.NonTransferable]);
+      const lamports = await provider.connection.getMinimumBalanceForRentExemption(mintLen);
+      await sendAndConfirmTransaction(
+        provider.connection,
+        new Transaction().add(
+          SystemProgram.createAccount({
+            fromPubkey: user.publicKey,
+            newAccountPubkey: frozenMint.publicKey,
+            space: mintLen,
+            lamports,
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          createInitializeNonTransferableMintInstruction(frozenMint.publicKey, TOKEN_2022_PROGRAM_ID),
+          createInitializeMintInstruction(frozenMint.publicKey, 6, user.publicKey, null, TOKEN_2022_PROGRAM_ID)
+        ),
+        [user, frozenMint]
+      );
 
-  //     console.log("Liquidity added with signature:", tx);
+      const [minta, mintb, tokenProgramA, tokenProgramB] =
+        Buffer.compare(mintA.toBuffer(), frozenMint.publicKey.toBuffer()) < 0
+          ? [mintA, frozenMint.publicKey, TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID]
+          : [frozenMint.publicKey, mintA, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID];
+      const frozenPool = poolAddress(minta, mintb);
 
-  //     // Verify LP tokens were minted
-  //     const lpAccount = await getAccount(provider.connection, userLpAccount);
-  //     expect(Number(lpAccount.amount)).to.be.greaterThan(0);
+      await program.methods
+        .initPool(INITIAL_PRICE, FEE_RATE)
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+          minta,
+          mintb,
+          vaulta: getAssociatedTokenAddressSync(minta, frozenPool, true, tokenProgramA),
+          config: frozenPool,
+          vaultB: getAssociatedTokenAddressSync(mintb, frozenPool, true, tokenProgramB),
+          observation: observationAddress(frozenPool),
+          tickArrayBitmap: tickArrayBitmapAddress(frozenPool),
+          systemProgram: SystemProgram.programId,
+          tokenProgramA,
+          tokenProgramB,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
 
-  //     // Verify pool liquidity increased
-  //     const poolAccount = await program.account.pool.fetch(pool);
-  //     expect(poolAccount.activeLiqiudity.toNumber()).to.be.greaterThan(0);
-  //   } catch (error) {
-  //     console.error("Error adding liquidity:", error);
-  //     throw error;
-  //   }
-  // });
+      expect.fail("Expected init_pool to fail");
+    } catch (error) {
+      expect(error.message).to.include("UnsupportedMintExtension");
+    }
+  });
 
-  // // it("Swap tokens", async () => {
-  // //   try {
-  // //     const amountIn = new anchor.BN(100000); // 0.1 tokens
-  // //     const aToB = true; // Swap from token A to token B
-  // //     const sqrtPriceLimit = null; // No price limit
-  // //     const minAmountOut = new anchor.BN(90000); // 0.09 tokens minimum output
-
-  // //     // Get initial balances
-  // //     const initialBalanceA = await getAccount(provider.connection, userTokenAccountA);
-  // //     const initialBalanceB = await getAccount(provider.connection, userTokenAccountB);
-
-  // //     const tx = await program.methods
-  // //       .swap(
-  // //         amountIn,
-  // //         sqrtPriceLimit,
-  // //         minAmountOut,
-  // //         aToB
-  // //       )
-  // //       .accountsStrict({
-  // //         useraccount: user.publicKey,
-  // //         minta: mintA,
-  // //         mintb: mintB,
-  // //         usertokenAccountA: userTokenAccountA,
-  // //         usertokenAccountB: userTokenAccountB,
-  // //         vaulta: vaultA,
-  // //         config: pool,
-  // //         vaultB: vaultB,
-  // //         tokenProgram: TOKEN_PROGRAM_ID,
-  // //         systemProgram:SYSTEM_PROGRAM_ID,
-  // //         lowertick:
-  // //       })
-  // //       .remainingAccounts([
-  // //         { pubkey: tickLower, isSigner: false, isWritable: true },
-  // //         { pubkey: tickUpper, isSigner: false, isWritable: true },
-  // //       ])
-  // //       .signers([user])
-  // //       .rpc();
-
-  // //     console.log("Swap executed with signature:", tx);
-
-  // //     // Verify balances changed
-  // //     const finalBalanceA = await getAccount(provider.connection, userTokenAccountA);
-  // //     const finalBalanceB = await getAccount(provider.connection, userTokenAccountB);
-
-  // //     expect(Number(finalBalanceA.amount)).to.be.lessThan(Number(initialBalanceA.amount));
-  // //     expect(Number(finalBalanceB.amount)).to.be.greaterThan(Number(initialBalanceB.amount));
-  // //   } catch (error) {
-  // //     console.error("Error executing swap:", error);
-  // //     throw error;
-  // //   }
-  // // });
-
-  // it("Withdraw liquidity", async () => {
-  //   try {
-  //     const liquidityToRemove = new anchor.BN(500000); // Remove half the liquidity
-  //     const tickLowerIndex = -100;
-  //     const tickUpperIndex = 100;
-
-  //     // Get initial LP balance
-  //     const initialLpBalance = await getAccount(provider.connection, userLpAccount);
-
-  //     const tx = await program.methods
-  //       .withdrawLiquidity(
-  //         tickLowerIndex,
-  //         tickUpperIndex,
-  //         liquidityToRemove
-  //       )
-  //       .accountsStrict
-  //       ({
-  //         signer: user.publicKey,
-  //         minta: mintA,
-  //         mintb: mintB,
-  //         lpMint: lpMint,
-  //         usertokenAccountA: userTokenAccountA,
-  //         usertokenAccountB: userTokenAccountB,
-  //         userLpAccount: userLpAccount,
-  //         vaulta: vaultA,
-  //         config: pool,
-  //         vaultB: vaultB,
-  //         uppertick: tickUpper,
-  //         lowertick: tickLower,
-  //         systemProgram: SystemProgram.programId,
-  //         tokenProgram: TOKEN_PROGRAM_ID,
-  //         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-  //       })
-  //       .signers([user])
-  //       .rpc();
+  it("Initialize tick arrays", async () => {
+    try {
+      // Initialize the array holding the lower tick
+      const tx1 = await program.methods
+        .initTickArray(-600)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: pool,
+          tickArray: tickArrayLower,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
 
-  //     console.log("Liquidity withdrawn with signature:", tx);
+      console.log("Lower tick array initialized with signature:", tx1);
 
-  //     // Verify LP tokens were burned
-  //     const finalLpBalance = await getAccount(provider.connection, userLpAccount);
-  //     expect(Number(finalLpBalance.amount)).to.be.lessThan(Number(initialLpBalance.amount));
+      // Initialize the array holding the upper tick
+      const tx2 = await program.methods
+        .initTickArray(0)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: pool,
+          tickArray: tickArrayUpper,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
 
-  //     // Verify pool liquidity decreased
-  //     const poolAccount = await program.account.pool.fetch(pool);
-  //     expect(poolAccount.activeLiqiudity.toNumber()).to.be.greaterThan(0);
-  //   } catch (error) {
-  //     console.error("Error withdrawing liquidity:", error);
-  //     throw error;
-  //   }
-  // });
+      console.log("Upper tick array initialized with signature:", tx2);
 
-  // it("Should fail with invalid tick range", async () => {
-  //   try {
-  //     const liquidity = new anchor.BN(1000000);
-  //     const tickLowerIndex = 100; // Upper tick
-  //     const tickUpperIndex = -100; // Lower tick (invalid order)
+      // Verify tick arrays were created
+      const lowerTickArray = await program.account.tickArray.fetch(tickArrayLower);
+      const upperTickArray = await program.account.tickArray.fetch(tickArrayUpper);
 
-  //     await program.methods
-  //       .addLiquidity(
-  //         tickLowerIndex,
-  //         tickUpperIndex,
-  //         liquidity
-  //       )
-  //       .accountsStrict({
-  //         signer: user.publicKey,
-  //         minta: mintA,
-  //         mintb: mintB,
-  //         lpMint: lpMint,
-  //         usertokenAccountA: userTokenAccountA,
-  //         usertokenAccountB: userTokenAccountB,
-  //         userLpAccount: userLpAccount,
-  //         vaulta: vaultA,
-  //         config: pool,
-  //         vaultB: vaultB,
-  //         uppertick: tickUpper,
-  //         lowertick: tickLower,
-  //         systemProgram: SystemProgram.programId,
-  //         tokenProgram: TOKEN_PROGRAM_ID,
-  //         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-  //       })
-  //       .signers([user])
-  //       .rpc();
+      expect(lowerTickArray.startTickIndex).to.equal(-600);
+      expect(upperTickArray.startTickIndex).to.equal(0);
+      expect(lowerTickArray.ticks[50].tick).to.equal(-100);
+    } catch (error) {
+      console.error("Error initializing tick arrays:", error);
+      throw error;
+    }
+  });
 
-  //     // Should not reach here
-  //     expect.fail("Should have thrown an error");
-  //   } catch (error) {
-  //     console.log("Expected error caught:", error.message);
-  //     expect(error.message).to.include("error");
-  //   }
-  // });
+  it("Open position", async () => {
+    try {
+      const tx = await program.methods
+        .openPosition(TICK_LOWER, TICK_UPPER)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: pool,
+          positionMint: positionMint.publicKey,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .signers([user, positionMint])
+        .rpc();
+
+      console.log("Position opened with signature:", tx);
+
+      // Verify the position token was minted and the range recorded
+      const positionToken = await getAccount(provider.connection, positionTokenAccount);
+      expect(Number(positionToken.amount)).to.equal(1);
+
+      const positionAccount = await program.account.position.fetch(position);
+      expect(positionAccount.tickLower).to.equal(TICK_LOWER);
+      expect(positionAccount.tickUpper).to.equal(TICK_UPPER);
+      expect(positionAccount.liquidity.toNumber()).to.equal(0);
+    } catch (error) {
+      console.error("Error opening position:", error);
+      throw error;
+    }
+  });
+
+  it("Increase liquidity", async () => {
+    try {
+      const liquidity = new anchor.BN(1000000); // 1M liquidity units
+
+      const tx = await program.methods
+        .increaseLiquidity(liquidity, new anchor.BN(5000), new anchor.BN(5000), false, null)
+        .accountsStrict({
+          signer: user.publicKey,
+          minta: mintA,
+          mintb: mintB,
+          usertokenAccountA: userTokenAccountA,
+          usertokenAccountB: userTokenAccountB,
+          wsolAccount: null,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          vaulta: vaultA,
+          config: pool,
+          vaultB: vaultB,
+          tickArrayLower: tickArrayLower,
+          tickArrayUpper: tickArrayUpper,
+          observation: observation,
+          tickArrayBitmap: tickArrayBitmap,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Liquidity added with signature:", tx);
+
+      // Verify the position holds the new liquidity
+      const positionAccount = await program.account.position.fetch(position);
+      expect(positionAccount.liquidity.toNumber()).to.equal(1000000);
+
+      // Verify the range ticks were initialized
+      const lowerTickArray = await program.account.tickArray.fetch(tickArrayLower);
+      expect(lowerTickArray.ticks[50].liquidityGross.toNumber()).to.equal(1000000);
+    } catch (error) {
+      console.error("Error adding liquidity:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to increase liquidity above the token maximums", async () => {
+    try {
+      await program.methods
+        .increaseLiquidity(new anchor.BN(1000000), new anchor.BN(1), new anchor.BN(1), false, null)
+        .accountsStrict({
+          signer: user.publicKey,
+          minta: mintA,
+          mintb: mintB,
+          usertokenAccountA: userTokenAccountA,
+          usertokenAccountB: userTokenAccountB,
+          wsolAccount: null,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          vaulta: vaultA,
+          config: pool,
+          vaultB: vaultB,
+          tickArrayLower: tickArrayLower,
+          tickArrayUpper: tickArrayUpper,
+          observation: observation,
+          tickArrayBitmap: tickArrayBitmap,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected increase_liquidity to fail");
+    } catch (error) {
+      expect(error.message).to.include("TokenMaxExceeded");
+    }
+  });
+
+  it("Increase liquidity by amounts", async () => {
+    try {
+      const amountAMax = new anchor.BN(5000);
+      const amountBMax = new anchor.BN(5000);
+
+      const initialVaultA = Number((await getAccount(provider.connection, vaultA)).amount);
+      const initialVaultB = Number((await getAccount(provider.connection, vaultB)).amount);
+
+      const tx = await program.methods
+        .increaseLiquidityByAmounts(amountAMax, amountBMax, null, false, null)
+        .accountsStrict({
+          signer: user.publicKey,
+          minta: mintA,
+          mintb: mintB,
+          usertokenAccountA: userTokenAccountA,
+          usertokenAccountB: userTokenAccountB,
+          wsolAccount: null,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          vaulta: vaultA,
+          config: pool,
+          vaultB: vaultB,
+          tickArrayLower: tickArrayLower,
+          tickArrayUpper: tickArrayUpper,
+          observation: observation,
+          tickArrayBitmap: tickArrayBitmap,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Liquidity added by amounts with signature:", tx);
+
+      // Roughly another million liquidity fits, without going over either maximum
+      const positionAccount = await program.account.position.fetch(position);
+      expect(positionAccount.liquidity.toNumber()).to.be.greaterThan(1900000);
+
+      const paidA = Number((await getAccount(provider.connection, vaultA)).amount) - initialVaultA;
+      const paidB = Number((await getAccount(provider.connection, vaultB)).amount) - initialVaultB;
+      expect(paidA).to.be.at.most(5000);
+      expect(paidB).to.be.at.most(5000);
+    } catch (error) {
+      console.error("Error adding liquidity by amounts:", error);
+      throw error;
+    }
+  });
+
+  it("Initialize reward", async () => {
+    try {
+      const tx = await program.methods
+        .initializeReward(0, user.publicKey)
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+          config: pool,
+          rewardMint: rewardMint,
+          rewardVault: rewardVault,
+          rewardTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Reward initialized with signature:", tx);
+
+      const poolAccount = await program.account.pool.fetch(pool);
+      expect(poolAccount.rewardInfos[0].mint.toString()).to.equal(rewardMint.toString());
+      expect(poolAccount.rewardInfos[0].vault.toString()).to.equal(rewardVault.toString());
+      expect(poolAccount.rewardInfos[0].authority.toString()).to.equal(user.publicKey.toString());
+      expect(poolAccount.rewardInfos[0].emissionsPerSecondX64.toNumber()).to.equal(0);
+    } catch (error) {
+      console.error("Error initializing reward:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to set emissions the reward vault cannot cover", async () => {
+    try {
+      // Nothing has been deposited yet, so any rate is more than a day ahead of the vault
+      await program.methods
+        .setRewardEmissions(0, new anchor.BN(1000).shln(64))
+        .accountsStrict({ signer: user.publicKey, config: pool, rewardVault: rewardVault })
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected set_reward_emissions to fail");
+    } catch (error) {
+      expect(error.message).to.include("InsufficientRewardVaultBalance");
+    }
+  });
+
+  it("Set reward emissions", async () => {
+    try {
+      // Fund more than a day at 1000 tokens a second
+      await mintTo(provider.connection, user, rewardMint, rewardVault, user, 100_000_000);
+
+      const tx = await program.methods
+        .setRewardEmissions(0, new anchor.BN(1000).shln(64))
+        .accountsStrict({ signer: user.publicKey, config: pool, rewardVault: rewardVault })
+        .signers([user])
+        .rpc();
+
+      console.log("Reward emissions set with signature:", tx);
+
+      const poolAccount = await program.account.pool.fetch(pool);
+      expect(poolAccount.rewardInfos[0].emissionsPerSecondX64.toString()).to.equal(new anchor.BN(1000).shln(64).toString());
+    } catch (error) {
+      console.error("Error setting reward emissions:", error);
+      throw error;
+    }
+  });
+
+  const swapAccounts = () => ({
+    useraccount: user.publicKey,
+    pooladmint: user.publicKey,
+    minta: mintA,
+    mintb: mintB,
+    usertokenAccountA: userTokenAccountA,
+    usertokenAccountB: userTokenAccountB,
+    wsolAccount: null,
+    vaulta: vaultA,
+    config: pool,
+    vaultB: vaultB,
+    observation: observation,
+    tickArrayBitmap: tickArrayBitmap,
+    systemProgram: SystemProgram.programId,
+    tokenProgramA: TOKEN_PROGRAM_ID,
+    tokenProgramB: TOKEN_PROGRAM_ID,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    eventAuthority: eventAuthority,
+    program: program.programId,
+  });
+
+  // Events emitted with emit_cpi! are the data of inner self-CPIs, after an 8-byte tag
+  const cpiEvents = async (signature: string) => {
+    const txInfo = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    return txInfo.meta.innerInstructions
+      .flatMap((inner) => inner.instructions)
+      .map((ix) => anchor.utils.bytes.bs58.decode(ix.data))
+      .filter((data) => data.length > 8)
+      .map((data) => program.coder.events.decode(anchor.utils.bytes.base64.encode(Buffer.from(data.subarray(8)))))
+      .filter((event) => event !== null);
+  };
+
+  const balance = async (account: PublicKey, tokenProgram = TOKEN_PROGRAM_ID) =>
+    Number((await getAccount(provider.connection, account, undefined, tokenProgram)).amount);
+
+  // Arrays an A to B swap walks through, starting from the one holding the current tick
+  const aToBTickArrays = async () => {
+    const { currentTick } = await program.account.pool.fetch(pool);
+    return currentTick >= 0 ? [tickArrayUpper, tickArrayLower] : [tickArrayLower];
+  };
+
+  it("Swap A to B", async () => {
+    try {
+      const amountIn = new anchor.BN(1000);
+
+      const initialUserA = await balance(userTokenAccountA);
+      const initialUserB = await balance(userTokenAccountB);
+      const initialVaultA = await balance(vaultA);
+      const initialVaultB = await balance(vaultB);
+
+      // Price moves down, so the array holding tick 0 comes first, then the one holding -100
+      const tx = await program.methods
+        .swap(amountIn, null, null, null, true, true, false, null)
+        .accountsStrict(swapAccounts())
+        .remainingAccounts([
+          { pubkey: tickArrayUpper, isSigner: false, isWritable: true },
+          { pubkey: tickArrayLower, isSigner: false, isWritable: true },
+        ])
+        .signers([user])
+        .rpc();
+
+      console.log("Swap A to B executed with signature:", tx);
+
+      // Whatever the user paid in A landed in the vault, and the B they received left it
+      const paidA = initialUserA - (await balance(userTokenAccountA));
+      const receivedB = (await balance(userTokenAccountB)) - initialUserB;
+      expect(paidA).to.be.greaterThan(0);
+      expect(receivedB).to.be.greaterThan(0);
+      expect((await balance(vaultA)) - initialVaultA).to.equal(paidA);
+      expect(initialVaultB - (await balance(vaultB))).to.equal(receivedB);
+
+      // The swap is reported with what actually moved
+      const swapped = (await cpiEvents(tx)).find((event) => event.name === "swapped");
+      expect(swapped).to.not.be.undefined;
+      expect(swapped.data.aToB).to.be.true;
+      expect(swapped.data.amountIn.toNumber()).to.equal(paidA);
+      expect(swapped.data.amountOut.toNumber()).to.equal(receivedB);
+    } catch (error) {
+      console.error("Error executing swap:", error);
+      throw error;
+    }
+  });
+
+  it("Swap B to A", async () => {
+    try {
+      const amountIn = new anchor.BN(1000);
+
+      const initialUserA = await balance(userTokenAccountA);
+      const initialUserB = await balance(userTokenAccountB);
+      const initialVaultA = await balance(vaultA);
+      const initialVaultB = await balance(vaultB);
+
+      // The previous swap left the price just below tick 0, so the array holding it comes first
+      const tx = await program.methods
+        .swap(amountIn, null, null, null, true, false, false, null)
+        .accountsStrict(swapAccounts())
+        .remainingAccounts([
+          { pubkey: tickArrayLower, isSigner: false, isWritable: true },
+          { pubkey: tickArrayUpper, isSigner: false, isWritable: true },
+        ])
+        .signers([user])
+        .rpc();
+
+      console.log("Swap B to A executed with signature:", tx);
+
+      // Whatever the user paid in B landed in the vault, and the A they received left it
+      const paidB = initialUserB - (await balance(userTokenAccountB));
+      const receivedA = (await balance(userTokenAccountA)) - initialUserA;
+      expect(paidB).to.be.greaterThan(0);
+      expect(receivedA).to.be.greaterThan(0);
+      expect((await balance(vaultB)) - initialVaultB).to.equal(paidB);
+      expect(initialVaultA - (await balance(vaultA))).to.equal(receivedA);
+    } catch (error) {
+      console.error("Error executing swap:", error);
+      throw error;
+    }
+  });
+
+  it("Observe", async () => {
+    try {
+      const { tickCumulatives, secondsPerLiquidityCumulativesX64 } = await program.methods
+        .observe([0, 1])
+        .accountsStrict({ config: pool, observation: observation })
+        .view();
+
+      // One entry per requested time, and cumulative values never run backwards in time
+      expect(tickCumulatives.length).to.equal(2);
+      expect(secondsPerLiquidityCumulativesX64[0].gte(secondsPerLiquidityCumulativesX64[1])).to.be.true;
+    } catch (error) {
+      console.error("Error observing:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to observe before the oldest observation", async () => {
+    try {
+      await program.methods
+        .observe([1_000_000])
+        .accountsStrict({ config: pool, observation: observation })
+        .view();
+
+      expect.fail("Expected observe to fail");
+    } catch (error) {
+      expect(error.message).to.include("ObservationTooOld");
+    }
+  });
+
+  it("Observe across a liquidity change", async () => {
+    try {
+      // Let the clock move so the change lands on a new timestamp
+      await new Promise((resolve) => setTimeout(resolve, 2000));
+      const poolBefore = await program.account.pool.fetch(pool);
+      expect(poolBefore.currentTick).to.be.within(TICK_LOWER, TICK_UPPER - 1);
+
+      const tx = await program.methods
+        .increaseLiquidity(new anchor.BN(100000), new anchor.BN(5000), new anchor.BN(5000), false, null)
+        .accountsStrict({
+          signer: user.publicKey,
+          minta: mintA,
+          mintb: mintB,
+          usertokenAccountA: userTokenAccountA,
+          usertokenAccountB: userTokenAccountB,
+          wsolAccount: null,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          vaulta: vaultA,
+          config: pool,
+          vaultB: vaultB,
+          tickArrayLower: tickArrayLower,
+          tickArrayUpper: tickArrayUpper,
+          observation: observation,
+          tickArrayBitmap: tickArrayBitmap,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("In-range liquidity changed with signature:", tx);
+
+      // The change first wrote an observation holding the liquidity in range up to it
+      const state = await program.account.observationState.fetch(observation);
+      const latest = state.observations[state.observationIndex];
+      const previous =
+        state.observations[(state.observationIndex + state.observationCardinality - 1) % state.observationCardinality];
+      const elapsed = latest.blockTimestamp - previous.blockTimestamp;
+      expect(elapsed).to.be.greaterThan(0);
+      expect(latest.tickCumulative.sub(previous.tickCumulative).toNumber()).to.equal(poolBefore.currentTick * elapsed);
+      expect(latest.secondsPerLiquidityCumulativeX64.sub(previous.secondsPerLiquidityCumulativeX64).toString()).to.equal(
+        new anchor.BN(elapsed).shln(64).div(poolBefore.activeLiqiudity).toString()
+      );
+
+      // Observing now carries on from that observation
+      const { secondsPerLiquidityCumulativesX64 } = await program.methods
+        .observe([0])
+        .accountsStrict({ config: pool, observation: observation })
+        .view();
+      expect(secondsPerLiquidityCumulativesX64[0].gte(latest.secondsPerLiquidityCumulativeX64)).to.be.true;
+    } catch (error) {
+      console.error("Error observing across a liquidity change:", error);
+      throw error;
+    }
+  });
+
+  const flashAccounts = () => ({
+    signer: user.publicKey,
+    minta: mintA,
+    mintb: mintB,
+    usertokenAccountA: userTokenAccountA,
+    usertokenAccountB: userTokenAccountB,
+    config: pool,
+    vaulta: vaultA,
+    vaultB: vaultB,
+    tokenProgramA: TOKEN_PROGRAM_ID,
+    tokenProgramB: TOKEN_PROGRAM_ID,
+  });
+
+  it("Flash borrow and repay", async () => {
+    try {
+      const initialVaultA = await balance(vaultA);
+
+      // Borrow and repay have to land in the same transaction
+      const borrowIx = await program.methods
+        .flashBorrow(new anchor.BN(1000), new anchor.BN(0))
+        .accountsStrict({ ...flashAccounts(), instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY })
+        .instruction();
+      const repayIx = await program.methods.flashRepay().accountsStrict(flashAccounts()).instruction();
+      const tx = await provider.sendAndConfirm(new Transaction().add(borrowIx, repayIx), [user]);
+
+      console.log("Flash loan executed with signature:", tx);
+
+      // The loan came back with the 0.05% fee, rounded up
+      expect((await balance(vaultA)) - initialVaultA).to.equal(1);
+      const poolAccount = await program.account.pool.fetch(pool);
+      expect(poolAccount.locked).to.be.false;
+      expect(poolAccount.flashLoanAmountA.toNumber()).to.equal(0);
+    } catch (error) {
+      console.error("Error executing flash loan:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to flash borrow without a repay", async () => {
+    try {
+      await program.methods
+        .flashBorrow(new anchor.BN(1000), new anchor.BN(0))
+        .accountsStrict({ ...flashAccounts(), instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY })
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected flash_borrow to fail");
+    } catch (error) {
+      expect(error.message).to.include("MissingFlashRepay");
+    }
+  });
+
+  // A flash swap's callback here is the token program itself: the caller's data is a
+  // plain Transfer of `payA` from the user to vault A, which ignores the amounts appended after it
+  const flashSwapAToB = async (amountIn: anchor.BN, payA: anchor.BN) => {
+    const tickArrays = await aToBTickArrays();
+    const transferData = Buffer.concat([Buffer.from([3]), payA.toArrayLike(Buffer, "le", 8)]);
+    return program.methods
+      .flashSwap(amountIn, null, true, true, tickArrays.length, transferData, null)
+      .accountsStrict(swapAccounts())
+      .remainingAccounts([
+        ...tickArrays.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })),
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: userTokenAccountA, isSigner: false, isWritable: true },
+        { pubkey: vaultA, isSigner: false, isWritable: true },
+        { pubkey: user.publicKey, isSigner: true, isWritable: false },
+      ])
+      .signers([user])
+      .rpc();
+  };
+
+  it("Flash swap A to B", async () => {
+    try {
+      const amountIn = new anchor.BN(1000);
+
+      const initialUserA = await balance(userTokenAccountA);
+      const initialUserB = await balance(userTokenAccountB);
+      const initialVaultA = await balance(vaultA);
+
+      const tx = await flashSwapAToB(amountIn, amountIn);
+
+      console.log("Flash swap executed with signature:", tx);
+
+      // The output went out before the callback paid the input in
+      const receivedB = (await balance(userTokenAccountB)) - initialUserB;
+      expect(receivedB).to.be.greaterThan(0);
+      expect(initialUserA - (await balance(userTokenAccountA))).to.equal(1000);
+      expect((await balance(vaultA)) - initialVaultA).to.equal(1000);
+      expect((await program.account.pool.fetch(pool)).locked).to.be.false;
+
+      const swapped = (await cpiEvents(tx)).find((event) => event.name === "swapped");
+      expect(swapped.data.amountIn.toNumber()).to.equal(1000);
+      expect(swapped.data.amountOut.toNumber()).to.equal(receivedB);
+    } catch (error) {
+      console.error("Error executing flash swap:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to flash swap when the callback underpays", async () => {
+    try {
+      await flashSwapAToB(new anchor.BN(1000), new anchor.BN(1));
+
+      expect.fail("Expected flash_swap to fail");
+    } catch (error) {
+      expect(error.message).to.include("FlashSwapNotPaid");
+    }
+  });
+
+  // A route of just this pool: pool, observation, bitmap extension, vaults, mints and token programs, then tick arrays
+  const swapRouteAToB = async (amountIn: anchor.BN, minAmountOut: anchor.BN) => {
+    const tickArrays = await aToBTickArrays();
+    return program.methods
+      .swapRoute(amountIn, minAmountOut, Buffer.from([tickArrays.length]), null)
+      .accountsStrict({
+        useraccount: user.publicKey,
+        mintIn: mintA,
+        mintOut: mintB,
+        usertokenAccountIn: userTokenAccountA,
+        usertokenAccountOut: userTokenAccountB,
+        systemProgram: SystemProgram.programId,
+        tokenProgramIn: TOKEN_PROGRAM_ID,
+        tokenProgramOut: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts([
+        { pubkey: pool, isSigner: false, isWritable: true },
+        { pubkey: observation, isSigner: false, isWritable: true },
+        { pubkey: tickArrayBitmap, isSigner: false, isWritable: false },
+        { pubkey: vaultA, isSigner: false, isWritable: true },
+        { pubkey: vaultB, isSigner: false, isWritable: true },
+        { pubkey: mintA, isSigner: false, isWritable: false },
+        { pubkey: mintB, isSigner: false, isWritable: false },
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        ...tickArrays.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })),
+      ])
+      .signers([user])
+      .rpc();
+  };
+
+  it("Swap route", async () => {
+    try {
+      const initialUserA = await balance(userTokenAccountA);
+      const initialUserB = await balance(userTokenAccountB);
+      const initialVaultB = await balance(vaultB);
+      const initialSqrtPrice = (await program.account.pool.fetch(pool)).sqrtPrice;
+
+      const tx = await swapRouteAToB(new anchor.BN(1000), new anchor.BN(1));
+
+      console.log("Swap route executed with signature:", tx);
+
+      const receivedB = (await balance(userTokenAccountB)) - initialUserB;
+      expect(initialUserA - (await balance(userTokenAccountA))).to.equal(1000);
+      expect(receivedB).to.be.greaterThan(0);
+      expect(initialVaultB - (await balance(vaultB))).to.equal(receivedB);
+      // The pool's new state was written back, with the price moved down
+      expect((await program.account.pool.fetch(pool)).sqrtPrice.lt(initialSqrtPrice)).to.be.true;
+
+      const swapped = (await cpiEvents(tx)).filter((event) => event.name === "swapped");
+      expect(swapped).to.have.lengthOf(1);
+      expect(swapped[0].data.amountOut.toNumber()).to.equal(receivedB);
+    } catch (error) {
+      console.error("Error executing swap route:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to swap a route below the minimum output", async () => {
+    try {
+      await swapRouteAToB(new anchor.BN(1000), new anchor.BN(1_000_000));
+
+      expect.fail("Expected swap_route to fail");
+    } catch (error) {
+      expect(error.message).to.include("SlippageExceeded");
+    }
+  });
+
+  it("Should fail to swap after the deadline", async () => {
+    try {
+      // Any timestamp in the past has expired
+      const deadline = new anchor.BN(Math.floor(Date.now() / 1000) - 60);
+
+      await program.methods
+        .swap(new anchor.BN(1000), null, null, null, true, true, false, deadline)
+        .accountsStrict(swapAccounts())
+        .remainingAccounts([
+          { pubkey: tickArrayLower, isSigner: false, isWritable: true },
+          { pubkey: tickArrayUpper, isSigner: false, isWritable: true },
+        ])
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected swap to fail");
+    } catch (error) {
+      expect(error.message).to.include("TransactionExpired");
+    }
+  });
+
+  it("Swap A to B for an exact output", async () => {
+    try {
+      const amountOut = new anchor.BN(500);
+
+      const initialUserA = await balance(userTokenAccountA);
+      const initialUserB = await balance(userTokenAccountB);
+      const initialVaultA = await balance(vaultA);
+      const initialVaultB = await balance(vaultB);
+
+      // The input is whatever the curve asks for, capped by max_amount_in
+      const tx = await program.methods
+        .swap(amountOut, null, null, new anchor.BN(1000), false, true, false, null)
+        .accountsStrict(swapAccounts())
+        .remainingAccounts((await aToBTickArrays()).map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+        .signers([user])
+        .rpc();
+
+      console.log("Exact output swap executed with signature:", tx);
+
+      // Exactly the requested B left the vault, and whatever A it cost came in
+      expect((await balance(userTokenAccountB)) - initialUserB).to.equal(500);
+      expect(initialVaultB - (await balance(vaultB))).to.equal(500);
+      const paidA = initialUserA - (await balance(userTokenAccountA));
+      expect(paidA).to.be.greaterThan(0);
+      expect(paidA).to.be.at.most(1000);
+      expect((await balance(vaultA)) - initialVaultA).to.equal(paidA);
+    } catch (error) {
+      console.error("Error executing exact output swap:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail an exact output swap costing more than max_amount_in", async () => {
+    try {
+      // 500 B out costs about 500 A at a price near 1, well over the 400 allowed
+      await program.methods
+        .swap(new anchor.BN(500), null, null, new anchor.BN(400), false, true, false, null)
+        .accountsStrict(swapAccounts())
+        .remainingAccounts((await aToBTickArrays()).map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected swap to fail");
+    } catch (error) {
+      expect(error.message).to.include("SlippageExceeded");
+    }
+  });
+
+  // What crossing a tick leaves as its outside fee growth, wrapping like the program does
+  const flippedGrowth = (global: anchor.BN, outside: anchor.BN) => global.sub(outside).umod(new anchor.BN(1).shln(128));
+  // sqrt(1.0001^tick) in Q64.64, close enough to serve as a price limit
+  const sqrtPriceAtTick = (tick: number) =>
+    new anchor.BN(BigInt(Math.floor(Math.pow(1.0001, tick / 2) * 2 ** 64)).toString());
+  let sqrtPriceBeforeCrossing: anchor.BN;
+
+  it("Swap A to B across the lower tick", async () => {
+    try {
+      const poolBefore = await program.account.pool.fetch(pool);
+      const tickBefore = (await program.account.tickArray.fetch(tickArrayLower)).ticks[50];
+      sqrtPriceBeforeCrossing = poolBefore.sqrtPrice;
+
+      // Stop just past tick -100, where the position's liquidity ends
+      const tx = await program.methods
+        .swap(new anchor.BN(1000000), sqrtPriceAtTick(-110), null, null, true, true, false, null)
+        .accountsStrict(swapAccounts())
+        .remainingAccounts((await aToBTickArrays()).map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+        .signers([user])
+        .rpc();
+
+      console.log("Swap across tick -100 executed with signature:", tx);
+
+      const poolAfter = await program.account.pool.fetch(pool);
+      const tickAfter = (await program.account.tickArray.fetch(tickArrayLower)).ticks[50];
+      expect(tickAfter.tick).to.equal(-100);
+      expect(poolAfter.currentTick).to.be.lessThan(-100);
+      // Moving down takes away what crossing upwards adds
+      expect(poolAfter.activeLiqiudity.toString()).to.equal(
+        poolBefore.activeLiqiudity.sub(tickBefore.liquidityNet).toString()
+      );
+      expect(tickAfter.feeGrowthOutsideA.toString()).to.equal(
+        flippedGrowth(poolAfter.feeGrowthGlobalA, tickBefore.feeGrowthOutsideA).toString()
+      );
+      expect(tickAfter.feeGrowthOutsideB.toString()).to.equal(
+        flippedGrowth(poolAfter.feeGrowthGlobalB, tickBefore.feeGrowthOutsideB).toString()
+      );
+    } catch (error) {
+      console.error("Error swapping across tick -100:", error);
+      throw error;
+    }
+  });
+
+  it("Swap B to A back across the lower tick", async () => {
+    try {
+      const poolBefore = await program.account.pool.fetch(pool);
+      const tickBefore = (await program.account.tickArray.fetch(tickArrayLower)).ticks[50];
+
+      // Back to the price before the previous swap, so later tests see the same pool
+      const tx = await program.methods
+        .swap(new anchor.BN(1000000), sqrtPriceBeforeCrossing, null, null, true, false, false, null)
+        .accountsStrict(swapAccounts())
+        .remainingAccounts([
+          { pubkey: tickArrayLower, isSigner: false, isWritable: true },
+          { pubkey: tickArrayUpper, isSigner: false, isWritable: true },
+        ])
+        .signers([user])
+        .rpc();
+
+      console.log("Swap back across tick -100 executed with signature:", tx);
+
+      const poolAfter = await program.account.pool.fetch(pool);
+      const tickAfter = (await program.account.tickArray.fetch(tickArrayLower)).ticks[50];
+      expect(poolAfter.sqrtPrice.toString()).to.equal(sqrtPriceBeforeCrossing.toString());
+      expect(poolAfter.currentTick).to.be.at.least(-100);
+      expect(poolAfter.activeLiqiudity.toString()).to.equal(
+        poolBefore.activeLiqiudity.add(tickBefore.liquidityNet).toString()
+      );
+      expect(tickAfter.feeGrowthOutsideA.toString()).to.equal(
+        flippedGrowth(poolAfter.feeGrowthGlobalA, tickBefore.feeGrowthOutsideA).toString()
+      );
+      expect(tickAfter.feeGrowthOutsideB.toString()).to.equal(
+        flippedGrowth(poolAfter.feeGrowthGlobalB, tickBefore.feeGrowthOutsideB).toString()
+      );
+    } catch (error) {
+      console.error("Error swapping back across tick -100:", error);
+      throw error;
+    }
+  });
+
+  it("Decrease liquidity", async () => {
+    try {
+      const liquidityToRemove = new anchor.BN(500000);
+      const initialLiquidity = (await program.account.position.fetch(position)).liquidity;
+
+      const tx = await program.methods
+        .decreaseLiquidity(liquidityToRemove, new anchor.BN(0), new anchor.BN(0), false, null)
+        .accountsStrict({
+          signer: user.publicKey,
+          minta: mintA,
+          mintb: mintB,
+          usertokenAccountA: userTokenAccountA,
+          usertokenAccountB: userTokenAccountB,
+          wsolAccount: null,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          vaulta: vaultA,
+          config: pool,
+          vaultB: vaultB,
+          tickArrayLower: tickArrayLower,
+          tickArrayUpper: tickArrayUpper,
+          observation: observation,
+          tickArrayBitmap: tickArrayBitmap,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Liquidity withdrawn with signature:", tx);
+
+      // Verify the position liquidity decreased
+      const positionAccount = await program.account.position.fetch(position);
+      expect(positionAccount.liquidity.toNumber()).to.equal(initialLiquidity.sub(liquidityToRemove).toNumber());
+    } catch (error) {
+      console.error("Error withdrawing liquidity:", error);
+      throw error;
+    }
+  });
+
+  it("Collect fees", async () => {
+    try {
+      // Decreasing liquidity settled the swaps' fees into the position, and nothing has traded since
+      const owed = await program.account.position.fetch(position);
+      const owedA = owed.tokensOwedA.toNumber();
+      const owedB = owed.tokensOwedB.toNumber();
+      expect(owedA).to.be.greaterThan(0);
+      expect(owedB).to.be.greaterThan(0);
+      const initialUserA = await balance(userTokenAccountA);
+      const initialUserB = await balance(userTokenAccountB);
+
+      const tx = await program.methods
+        .collectFees()
+        .accountsStrict({
+          signer: user.publicKey,
+          minta: mintA,
+          mintb: mintB,
+          usertokenAccountA: userTokenAccountA,
+          usertokenAccountB: userTokenAccountB,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          vaulta: vaultA,
+          config: pool,
+          vaultB: vaultB,
+          tickArrayLower: tickArrayLower,
+          tickArrayUpper: tickArrayUpper,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Fees collected with signature:", tx);
+
+      // Verify the user was paid exactly what was owed, and nothing is left owed to the position
+      expect((await balance(userTokenAccountA)) - initialUserA).to.equal(owedA);
+      expect((await balance(userTokenAccountB)) - initialUserB).to.equal(owedB);
+      const positionAccount = await program.account.position.fetch(position);
+      expect(positionAccount.tokensOwedA.toNumber()).to.equal(0);
+      expect(positionAccount.tokensOwedB.toNumber()).to.equal(0);
+    } catch (error) {
+      console.error("Error collecting fees:", error);
+      throw error;
+    }
+  });
+
+  it("Collect reward", async () => {
+    try {
+      // Let some emissions accrue to the position, the only liquidity in range
+      await new Promise((resolve) => setTimeout(resolve, 2000));
+      const userRewardAccount = getAssociatedTokenAddressSync(rewardMint, user.publicKey);
+
+      const tx = await program.methods
+        .collectReward(0)
+        .accountsStrict({
+          signer: user.publicKey,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          config: pool,
+          tickArrayLower: tickArrayLower,
+          tickArrayUpper: tickArrayUpper,
+          rewardMint: rewardMint,
+          rewardVault: rewardVault,
+          userRewardAccount: userRewardAccount,
+          rewardTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Reward collected with signature:", tx);
+
+      // Everything owed was paid out of the vault
+      const positionAccount = await program.account.position.fetch(position);
+      expect(positionAccount.rewardInfos[0].amountOwed.toNumber()).to.equal(0);
+      expect(await balance(userRewardAccount)).to.be.greaterThan(0);
+    } catch (error) {
+      console.error("Error collecting reward:", error);
+      throw error;
+    }
+  });
+
+  it("Collect protocol fees", async () => {
+    try {
+      const treasuryTokenAccountA = await createAssociatedTokenAccount(provider.connection, user, mintA, treasury.publicKey);
+      const treasuryTokenAccountB = await createAssociatedTokenAccount(provider.connection, user, mintB, treasury.publicKey);
+
+      const tx = await program.methods
+        .collectProtocolFees()
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+          minta: mintA,
+          mintb: mintB,
+          config: pool,
+          vaulta: vaultA,
+          vaultB: vaultB,
+          treasuryTokenAccountA: treasuryTokenAccountA,
+          treasuryTokenAccountB: treasuryTokenAccountB,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Protocol fees collected with signature:", tx);
+
+      // Verify the pool no longer owes anything to the protocol
+      const poolAccount = await program.account.pool.fetch(pool);
+      expect(poolAccount.protocolFeesOwedA.toNumber()).to.equal(0);
+      expect(poolAccount.protocolFeesOwedB.toNumber()).to.equal(0);
+    } catch (error) {
+      console.error("Error collecting protocol fees:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to close a position that still holds liquidity", async () => {
+    try {
+      await program.methods
+        .closePosition()
+        .accountsStrict({
+          signer: user.publicKey,
+          positionMint: positionMint.publicKey,
+          config: pool,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+
+      // Should not reach here
+      expect.fail("Should have thrown an error");
+    } catch (error) {
+      console.log("Expected error caught:", error.message);
+      expect(error.message).to.include("PositionNotEmpty");
+    }
+  });
+
+  it("Should fail with invalid tick range", async () => {
+    const badPositionMint = Keypair.generate();
+    const [badPosition] = PublicKey.findProgramAddressSync(
+      [Buffer.from("position"), badPositionMint.publicKey.toBuffer()],
+      program.programId
+    );
+    try {
+      await program.methods
+        .openPosition(TICK_UPPER, TICK_LOWER) // lower and upper swapped
+        .accountsStrict({
+          signer: user.publicKey,
+          config: pool,
+          positionMint: badPositionMint.publicKey,
+          positionTokenAccount: getAssociatedTokenAddressSync(badPositionMint.publicKey, user.publicKey),
+          position: badPosition,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .signers([user, badPositionMint])
+        .rpc();
+
+      // Should not reach here
+      expect.fail("Should have thrown an error");
+    } catch (error) {
+      console.log("Expected error caught:", error.message);
+      expect(error.message).to.include("TickMismatch");
+    }
+  });
+
+  it("Should fail to close a position with an unclaimed reward", async () => {
+    // Let emissions accrue again, then take out all liquidity, which settles them into the position
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+    const { liquidity } = await program.account.position.fetch(position);
+    const positionAccounts = {
+      signer: user.publicKey,
+      minta: mintA,
+      mintb: mintB,
+      usertokenAccountA: userTokenAccountA,
+      usertokenAccountB: userTokenAccountB,
+      positionTokenAccount: positionTokenAccount,
+      position: position,
+      vaulta: vaultA,
+      config: pool,
+      vaultB: vaultB,
+      tickArrayLower: tickArrayLower,
+      tickArrayUpper: tickArrayUpper,
+      tokenProgramA: TOKEN_PROGRAM_ID,
+      tokenProgramB: TOKEN_PROGRAM_ID,
+      eventAuthority: eventAuthority,
+      program: program.programId,
+    };
+    await program.methods
+      .decreaseLiquidity(liquidity, new anchor.BN(0), new anchor.BN(0), false, null)
+      .accountsStrict({
+        ...positionAccounts,
+        wsolAccount: null,
+        observation: observation,
+        tickArrayBitmap: tickArrayBitmap,
+        systemProgram: SystemProgram.programId,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+    await program.methods.collectFees().accountsStrict(positionAccounts).signers([user]).rpc();
+
+    // Only the reward is left in the position
+    const positionAccount = await program.account.position.fetch(position);
+    expect(positionAccount.liquidity.toNumber()).to.equal(0);
+    expect(positionAccount.tokensOwedA.toNumber()).to.equal(0);
+    expect(positionAccount.tokensOwedB.toNumber()).to.equal(0);
+    expect(positionAccount.rewardInfos[0].amountOwed.toNumber()).to.be.greaterThan(0);
+
+    try {
+      await program.methods
+        .closePosition()
+        .accountsStrict({
+          signer: user.publicKey,
+          positionMint: positionMint.publicKey,
+          config: pool,
+          positionTokenAccount: positionTokenAccount,
+          position: position,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected close_position to fail");
+    } catch (error) {
+      expect(error.message).to.include("PositionNotEmpty");
+    }
+  });
+
+  it("Close a Token-2022 position along with its mint", async () => {
+    try {
+      const nftMint = Keypair.generate();
+      const [nftPosition] = PublicKey.findProgramAddressSync(
+        [Buffer.from("position"), nftMint.publicKey.toBuffer()],
+        program.programId
+      );
+      const nftTokenAccount = getAssociatedTokenAddressSync(nftMint.publicKey, user.publicKey, false, TOKEN_2022_PROGRAM_ID);
+      await program.methods
+        .openPosition(TICK_LOWER, TICK_UPPER)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: pool,
+          positionMint: nftMint.publicKey,
+          positionTokenAccount: nftTokenAccount,
+          position: nftPosition,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .signers([user, nftMint])
+        .rpc();
+      const nftToken = await getAccount(provider.connection, nftTokenAccount, undefined, TOKEN_2022_PROGRAM_ID);
+      expect(Number(nftToken.amount)).to.equal(1);
+
+      const tx = await program.methods
+        .closePosition()
+        .accountsStrict({
+          signer: user.publicKey,
+          positionMint: nftMint.publicKey,
+          config: pool,
+          positionTokenAccount: nftTokenAccount,
+          position: nftPosition,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Token-2022 position closed with signature:", tx);
+
+      // The pool is the mint's close authority, so the mint goes with the token account and the position
+      expect(await provider.connection.getAccountInfo(nftTokenAccount)).to.be.null;
+      expect(await provider.connection.getAccountInfo(nftPosition)).to.be.null;
+      expect(await provider.connection.getAccountInfo(nftMint.publicKey)).to.be.null;
+    } catch (error) {
+      console.error("Error closing a Token-2022 position:", error);
+      throw error;
+    }
+  });
+
+  it("Increase liquidity in a tick array past the pool bitmap", async () => {
+    try {
+      // 600 ticks per array at spacing 10, so this array is 600 below tick 0's, past the pool's own 512
+      const farStart = -360000;
+      const farTickArray = tickArrayAddress(farStart);
+      const farPositionMint = Keypair.generate();
+      const [farPosition] = PublicKey.findProgramAddressSync(
+        [Buffer.from("position"), farPositionMint.publicKey.toBuffer()],
+        program.programId
+      );
+      const farPositionTokenAccount = getAssociatedTokenAddressSync(farPositionMint.publicKey, user.publicKey);
+
+      await program.methods
+        .initTickArray(farStart)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: pool,
+          tickArray: farTickArray,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+      await program.methods
+        .openPosition(farStart, farStart + 500)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: pool,
+          positionMint: farPositionMint.publicKey,
+          positionTokenAccount: farPositionTokenAccount,
+          position: farPosition,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .signers([user, farPositionMint])
+        .rpc();
+      const tx = await program.methods
+        .increaseLiquidity(new anchor.BN(1000000), new anchor.BN(5000), new anchor.BN(5000), false, null)
+        .accountsStrict({
+          signer: user.publicKey,
+          minta: mintA,
+          mintb: mintB,
+          usertokenAccountA: userTokenAccountA,
+          usertokenAccountB: userTokenAccountB,
+          wsolAccount: null,
+          positionTokenAccount: farPositionTokenAccount,
+          position: farPosition,
+          vaulta: vaultA,
+          config: pool,
+          vaultB: vaultB,
+          tickArrayLower: farTickArray,
+          tickArrayUpper: farTickArray,
+          observation: observation,
+          tickArrayBitmap: tickArrayBitmap,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Liquidity added past the pool bitmap with signature:", tx);
+
+      // The array is tracked by the extension's half below tick 0
+      const extension = await program.account.tickArrayBitmapExtension.fetch(tickArrayBitmap);
+      expect(extension.negativeBitmap.some((word) => !word.isZero())).to.be.true;
+      expect(extension.positiveBitmap.every((word) => word.isZero())).to.be.true;
+    } catch (error) {
+      console.error("Error adding liquidity past the pool bitmap:", error);
+      throw error;
+    }
+
+    // A swap down now has to be handed that array to get there
+    try {
+      await program.methods
+        .swap(new anchor.BN(1000), null, null, null, true, true, false, null)
+        .accountsStrict(swapAccounts())
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected swap to fail");
+    } catch (error) {
+      expect(error.message).to.include("MissingTickAccounts");
+    }
+  });
+
+  const lamports = async (account: PublicKey) => provider.connection.getBalance(account);
+
+  // The vault holding wSOL, and whether the pool takes it as token A
+  const nativeIsA = () => nativeMintA.equals(NATIVE_MINT);
+  const nativeVault = () => (nativeIsA() ? nativeVaultA : nativeVaultB);
+  const otherVault = () => (nativeIsA() ? nativeVaultB : nativeVaultA);
+
+  const nativeLiquidityAccounts = () => ({
+    signer: user.publicKey,
+    minta: nativeMintA,
+    mintb: nativeMintB,
+    usertokenAccountA: getAssociatedTokenAddressSync(nativeMintA, user.publicKey),
+    usertokenAccountB: getAssociatedTokenAddressSync(nativeMintB, user.publicKey),
+    wsolAccount: wsolAccount,
+    positionTokenAccount: getAssociatedTokenAddressSync(nativePositionMint.publicKey, user.publicKey),
+    position: nativePosition,
+    vaulta: nativeVaultA,
+    config: nativePool,
+    vaultB: nativeVaultB,
+    tickArrayLower: tickArrayAddress(-600, nativePool),
+    tickArrayUpper: tickArrayAddress(0, nativePool),
+    observation: observationAddress(nativePool),
+    tickArrayBitmap: tickArrayBitmapAddress(nativePool),
+    tokenProgramA: TOKEN_PROGRAM_ID,
+    tokenProgramB: TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    eventAuthority: eventAuthority,
+    program: program.programId,
+  });
+
+  // The native side moves through the ephemeral wSOL account, so it needs no token account of the user's
+  const nativeSwapAccounts = () => ({
+    useraccount: user.publicKey,
+    pooladmint: user.publicKey,
+    minta: nativeMintA,
+    mintb: nativeMintB,
+    usertokenAccountA: nativeIsA() ? null : getAssociatedTokenAddressSync(nativeMintA, user.publicKey),
+    usertokenAccountB: nativeIsA() ? getAssociatedTokenAddressSync(nativeMintB, user.publicKey) : null,
+    wsolAccount: wsolAccount,
+    vaulta: nativeVaultA,
+    config: nativePool,
+    vaultB: nativeVaultB,
+    observation: observationAddress(nativePool),
+    tickArrayBitmap: tickArrayBitmapAddress(nativePool),
+    systemProgram: SystemProgram.programId,
+    tokenProgramA: TOKEN_PROGRAM_ID,
+    tokenProgramB: TOKEN_PROGRAM_ID,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    eventAuthority: eventAuthority,
+    program: program.programId,
+  });
+
+  // Arrays a swap on one of the extra pools walks through, starting from the one holding the current tick
+  const poolTickArrays = async (forPool: PublicKey, aToB: boolean) => {
+    const { currentTick } = await program.account.pool.fetch(forPool);
+    const [lower, upper] = [tickArrayAddress(-600, forPool), tickArrayAddress(0, forPool)];
+    const tickArrays = aToB ? (currentTick >= 0 ? [upper, lower] : [lower]) : currentTick >= 0 ? [upper] : [lower, upper];
+    return tickArrays.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+  };
+
+  // Lamports moved through the ephemeral wSOL account must leave the user's own wSOL untouched
+  const expectWsolUntouched = async (initialWsol: number) => {
+    expect(await balance(userWsolAccount)).to.equal(initialWsol);
+    expect(await provider.connection.getAccountInfo(wsolAccount)).to.be.null;
+  };
+
+  it("Initialize a native SOL pool", async () => {
+    try {
+      [nativeMintA, nativeMintB] =
+        Buffer.compare(NATIVE_MINT.toBuffer(), mintA.toBuffer()) < 0 ? [NATIVE_MINT, mintA] : [mintA, NATIVE_MINT];
+      nativePool = poolAddress(nativeMintA, nativeMintB);
+      nativeVaultA = getAssociatedTokenAddressSync(nativeMintA, nativePool, true);
+      nativeVaultB = getAssociatedTokenAddressSync(nativeMintB, nativePool, true);
+      nativePositionMint = Keypair.generate();
+      [nativePosition] = PublicKey.findProgramAddressSync(
+        [Buffer.from("position"), nativePositionMint.publicKey.toBuffer()],
+        program.programId
+      );
+      [wsolAccount] = PublicKey.findProgramAddressSync([Buffer.from("wsol"), user.publicKey.toBuffer()], program.programId);
+      // wSOL the user already holds, which native SOL flows must not touch
+      userWsolAccount = await createWrappedNativeAccount(
+        provider.connection,
+        user,
+        user.publicKey,
+        anchor.web3.LAMPORTS_PER_SOL / 10
+      );
+
+      // wSOL has 9 decimals to token A's 6, so price the pool 1:1 in base units to keep the position in range
+      const nativePrice = nativeIsA()
+        ? { ratio: { numerator: new anchor.BN(1000), denominator: new anchor.BN(1) } }
+        : { ratio: { numerator: new anchor.BN(1), denominator: new anchor.BN(1000) } };
+
+      await program.methods
+        .initPool(nativePrice, FEE_RATE)
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+          minta: nativeMintA,
+          mintb: nativeMintB,
+          vaulta: nativeVaultA,
+          config: nativePool,
+          vaultB: nativeVaultB,
+          observation: observationAddress(nativePool),
+          tickArrayBitmap: tickArrayBitmapAddress(nativePool),
+          systemProgram: SystemProgram.programId,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+      for (const startTickIndex of [-600, 0]) {
+        await program.methods
+          .initTickArray(startTickIndex)
+          .accountsStrict({
+            signer: user.publicKey,
+            config: nativePool,
+            tickArray: tickArrayAddress(startTickIndex, nativePool),
+            systemProgram: SystemProgram.programId,
+            eventAuthority: eventAuthority,
+            program: program.programId,
+          })
+          .signers([user])
+          .rpc();
+      }
+      await program.methods
+        .openPosition(TICK_LOWER, TICK_UPPER)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: nativePool,
+          positionMint: nativePositionMint.publicKey,
+          positionTokenAccount: getAssociatedTokenAddressSync(nativePositionMint.publicKey, user.publicKey),
+          position: nativePosition,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .signers([user, nativePositionMint])
+        .rpc();
+
+      const poolAccount = await program.account.pool.fetch(nativePool);
+      expect([poolAccount.minta.toString(), poolAccount.mintb.toString()]).to.include(NATIVE_MINT.toString());
+    } catch (error) {
+      console.error("Error initializing the native SOL pool:", error);
+      throw error;
+    }
+  });
+
+  it("Increase liquidity paying native SOL", async () => {
+    try {
+      const initialLamports = await lamports(user.publicKey);
+      const initialWsol = await balance(userWsolAccount);
+      const initialVault = await balance(nativeVault());
+
+      const tx = await program.methods
+        .increaseLiquidity(new anchor.BN(1000000), new anchor.BN(10000), new anchor.BN(10000), true, null)
+        .accountsStrict(nativeLiquidityAccounts())
+        .signers([user])
+        .rpc();
+
+      console.log("Liquidity added with native SOL with signature:", tx);
+
+      // The fee payer is the provider wallet, so the user's lamports only moved into the vault
+      const paid = (await balance(nativeVault())) - initialVault;
+      expect(paid).to.be.greaterThan(0);
+      expect(initialLamports - (await lamports(user.publicKey))).to.equal(paid);
+      await expectWsolUntouched(initialWsol);
+    } catch (error) {
+      console.error("Error adding liquidity with native SOL:", error);
+      throw error;
+    }
+  });
+
+  it("Swap paying native SOL", async () => {
+    try {
+      const aToB = nativeIsA();
+      const initialLamports = await lamports(user.publicKey);
+      const initialWsol = await balance(userWsolAccount);
+      const initialVault = await balance(nativeVault());
+      const initialUserA = await balance(userTokenAccountA);
+
+      const tx = await program.methods
+        .swap(new anchor.BN(1000), null, null, null, true, aToB, true, null)
+        .accountsStrict(nativeSwapAccounts())
+        .remainingAccounts(await poolTickArrays(nativePool, aToB))
+        .signers([user])
+        .rpc();
+
+      console.log("Swap paying native SOL executed with signature:", tx);
+
+      const paid = (await balance(nativeVault())) - initialVault;
+      expect(paid).to.equal(1000);
+      expect(initialLamports - (await lamports(user.publicKey))).to.equal(paid);
+      expect((await balance(userTokenAccountA)) - initialUserA).to.be.greaterThan(0);
+      await expectWsolUntouched(initialWsol);
+    } catch (error) {
+      console.error("Error swapping native SOL in:", error);
+      throw error;
+    }
+  });
+
+  it("Swap receiving native SOL", async () => {
+    try {
+      const aToB = !nativeIsA();
+      const initialLamports = await lamports(user.publicKey);
+      const initialWsol = await balance(userWsolAccount);
+      const initialVault = await balance(nativeVault());
+      const initialOtherVault = await balance(otherVault());
+
+      const tx = await program.methods
+        .swap(new anchor.BN(1000), null, null, null, true, aToB, true, null)
+        .accountsStrict(nativeSwapAccounts())
+        .remainingAccounts(await poolTickArrays(nativePool, aToB))
+        .signers([user])
+        .rpc();
+
+      console.log("Swap receiving native SOL executed with signature:", tx);
+
+      const received = initialVault - (await balance(nativeVault()));
+      expect(received).to.be.greaterThan(0);
+      expect((await lamports(user.publicKey)) - initialLamports).to.equal(received);
+      expect((await balance(otherVault())) - initialOtherVault).to.equal(1000);
+      await expectWsolUntouched(initialWsol);
+    } catch (error) {
+      console.error("Error swapping native SOL out:", error);
+      throw error;
+    }
+  });
+
+  it("Decrease liquidity receiving native SOL", async () => {
+    try {
+      const initialLamports = await lamports(user.publicKey);
+      const initialWsol = await balance(userWsolAccount);
+      const initialVault = await balance(nativeVault());
+
+      const tx = await program.methods
+        .decreaseLiquidity(new anchor.BN(500000), new anchor.BN(0), new anchor.BN(0), true, null)
+        .accountsStrict(nativeLiquidityAccounts())
+        .signers([user])
+        .rpc();
+
+      console.log("Liquidity withdrawn as native SOL with signature:", tx);
+
+      const received = initialVault - (await balance(nativeVault()));
+      expect(received).to.be.greaterThan(0);
+      expect((await lamports(user.publicKey)) - initialLamports).to.equal(received);
+      await expectWsolUntouched(initialWsol);
+    } catch (error) {
+      console.error("Error withdrawing liquidity as native SOL:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to flash borrow when the repay names another pool", async () => {
+    const nativeFlashAccounts = {
+      ...flashAccounts(),
+      minta: nativeMintA,
+      mintb: nativeMintB,
+      usertokenAccountA: getAssociatedTokenAddressSync(nativeMintA, user.publicKey),
+      usertokenAccountB: getAssociatedTokenAddressSync(nativeMintB, user.publicKey),
+      config: nativePool,
+      vaulta: nativeVaultA,
+      vaultB: nativeVaultB,
+    };
+    // Borrow the side of the native pool that is not wSOL
+    const [nativeAmountA, nativeAmountB] = nativeIsA() ? [0, 1000] : [1000, 0];
+    const nativeBorrowIx = await program.methods
+      .flashBorrow(new anchor.BN(nativeAmountA), new anchor.BN(nativeAmountB))
+      .accountsStrict({ ...nativeFlashAccounts, instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY })
+      .instruction();
+    // Only the native pool is repaid, with the first pool tagged along as an extra account
+    const nativeRepayIx = await program.methods
+      .flashRepay()
+      .accountsStrict(nativeFlashAccounts)
+      .remainingAccounts([{ pubkey: pool, isSigner: false, isWritable: true }])
+      .instruction();
+
+    try {
+      await program.methods
+        .flashBorrow(new anchor.BN(1000), new anchor.BN(0))
+        .accountsStrict({ ...flashAccounts(), instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY })
+        .postInstructions([nativeBorrowIx, nativeRepayIx])
+        .signers([user])
+        .rpc();
+
+      expect.fail("Expected flash_borrow to fail");
+    } catch (error) {
+      expect(error.message).to.include("MissingFlashRepay");
+    }
+  });
+
+  // 1% of every transfer of the fee mint, rounded up, is withheld from the recipient
+  const TRANSFER_FEE_BPS = 100;
+  const transferFee = (amount: number) => Math.ceil((amount * TRANSFER_FEE_BPS) / 10000);
+
+  const feeIsA = () => feeMintA.equals(feeMint);
+  const feeVault = () => (feeIsA() ? feeVaultA : feeVaultB);
+  const feeBalance = (account: PublicKey) => balance(account, TOKEN_2022_PROGRAM_ID);
+  const userFeeAccount = () => getAssociatedTokenAddressSync(feeMint, user.publicKey, false, TOKEN_2022_PROGRAM_ID);
+  // Puts `feeSide` on whichever side of the pool the fee mint is, and `otherSide` on the other
+  const bySide = <T>(feeSide: T, otherSide: T): [T, T] => (feeIsA() ? [feeSide, otherSide] : [otherSide, feeSide]);
+
+  const feeLiquidityAccounts = () => ({
+    signer: user.publicKey,
+    minta: feeMintA,
+    mintb: feeMintB,
+    usertokenAccountA: getAssociatedTokenAddressSync(feeMintA, user.publicKey, false, feeTokenProgramA),
+    usertokenAccountB: getAssociatedTokenAddressSync(feeMintB, user.publicKey, false, feeTokenProgramB),
+    wsolAccount: null,
+    positionTokenAccount: getAssociatedTokenAddressSync(feePositionMint.publicKey, user.publicKey),
+    position: feePosition,
+    vaulta: feeVaultA,
+    config: feePool,
+    vaultB: feeVaultB,
+    tickArrayLower: tickArrayAddress(-600, feePool),
+    tickArrayUpper: tickArrayAddress(0, feePool),
+    observation: observationAddress(feePool),
+    tickArrayBitmap: tickArrayBitmapAddress(feePool),
+    tokenProgramA: feeTokenProgramA,
+    tokenProgramB: feeTokenProgramB,
+    systemProgram: SystemProgram.programId,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    eventAuthority: eventAuthority,
+    program: program.programId,
+  });
+
+  const feeSwap = async (
+    amount: number,
+    amountSpecifiedIsInput: boolean,
+    aToB: boolean,
+    bounds: { sqrtPriceLimit?: anchor.BN; minAmountOut?: number; maxAmountIn?: number } = {}
+  ) =>
+    program.methods
+      .swap(
+        new anchor.BN(amount),
+        bounds.sqrtPriceLimit ?? null,
+        bounds.minAmountOut === undefined ? null : new anchor.BN(bounds.minAmountOut),
+        bounds.maxAmountIn === undefined ? null : new anchor.BN(bounds.maxAmountIn),
+        amountSpecifiedIsInput,
+        aToB,
+        false,
+        null
+      )
+      .accountsStrict({
+        useraccount: user.publicKey,
+        pooladmint: user.publicKey,
+        minta: feeMintA,
+        mintb: feeMintB,
+        usertokenAccountA: getAssociatedTokenAddressSync(feeMintA, user.publicKey, false, feeTokenProgramA),
+        usertokenAccountB: getAssociatedTokenAddressSync(feeMintB, user.publicKey, false, feeTokenProgramB),
+        wsolAccount: null,
+        vaulta: feeVaultA,
+        config: feePool,
+        vaultB: feeVaultB,
+        observation: observationAddress(feePool),
+        tickArrayBitmap: tickArrayBitmapAddress(feePool),
+        systemProgram: SystemProgram.programId,
+        tokenProgramA: feeTokenProgramA,
+        tokenProgramB: feeTokenProgramB,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts(await poolTickArrays(feePool, aToB))
+      .signers([user])
+      .rpc();
+
+  // Swaps back to `sqrtPrice` so a swap can be repeated against the same curve
+  const restoreFeePoolPrice = async (sqrtPrice: anchor.BN, aToB: boolean) => {
+    await feeSwap(1000000, true, aToB, { sqrtPriceLimit: sqrtPrice });
+    expect((await program.account.pool.fetch(feePool)).sqrtPrice.toString()).to.equal(sqrtPrice.toString());
+  };
+
+  it("Initialize a transfer fee pool", async () => {
+    try {
+      const feeMintKeypair = Keypair.generate();
+      feeMint = feeMintKeypair.publicKey;
+      const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+      await sendAndConfirmTransaction(
+        provider.connection,
+        new Transaction().add(
+          SystemProgram.createAccount({
+            fromPubkey: user.publicKey,
+            newAccountPubkey: feeMint,
+            space: mintLen,
+            lamports: await provider.connection.getMinimumBalanceForRentExemption(mintLen),
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          createInitializeTransferFeeConfigInstruction(
+            feeMint,
+            user.publicKey,
+            user.publicKey,
+            TRANSFER_FEE_BPS,
+            BigInt(1_000_000_000),
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createInitializeMintInstruction(feeMint, 6, user.publicKey, null, TOKEN_2022_PROGRAM_ID)
+        ),
+        [user, feeMintKeypair]
+      );
+      const userFeeTokenAccount = await createAssociatedTokenAccount(
+        provider.connection,
+        user,
+        feeMint,
+        user.publicKey,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await mintTo(provider.connection, user, feeMint, userFeeTokenAccount, user, 1000000000, [], undefined, TOKEN_2022_PROGRAM_ID);
+
+      [feeMintA, feeMintB, feeTokenProgramA, feeTokenProgramB] =
+        Buffer.compare(mintA.toBuffer(), feeMint.toBuffer()) < 0
+          ? [mintA, feeMint, TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID]
+          : [feeMint, mintA, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID];
+      feePool = poolAddress(feeMintA, feeMintB);
+      feeVaultA = getAssociatedTokenAddressSync(feeMintA, feePool, true, feeTokenProgramA);
+      feeVaultB = getAssociatedTokenAddressSync(feeMintB, feePool, true, feeTokenProgramB);
+      feePositionMint = Keypair.generate();
+      [feePosition] = PublicKey.findProgramAddressSync(
+        [Buffer.from("position"), feePositionMint.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initPool(INITIAL_PRICE, FEE_RATE)
+        .accountsStrict({
+          signer: user.publicKey,
+          ammConfig: ammConfig,
+          minta: feeMintA,
+          mintb: feeMintB,
+          vaulta: feeVaultA,
+          config: feePool,
+          vaultB: feeVaultB,
+          observation: observationAddress(feePool),
+          tickArrayBitmap: tickArrayBitmapAddress(feePool),
+          systemProgram: SystemProgram.programId,
+          tokenProgramA: feeTokenProgramA,
+          tokenProgramB: feeTokenProgramB,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([user])
+        .rpc();
+      for (const startTickIndex of [-600, 0]) {
+        await program.methods
+          .initTickArray(startTickIndex)
+          .accountsStrict({
+            signer: user.publicKey,
+            config: feePool,
+            tickArray: tickArrayAddress(startTickIndex, feePool),
+            systemProgram: SystemProgram.programId,
+            eventAuthority: eventAuthority,
+            program: program.programId,
+          })
+          .signers([user])
+          .rpc();
+      }
+      await program.methods
+        .openPosition(TICK_LOWER, TICK_UPPER)
+        .accountsStrict({
+          signer: user.publicKey,
+          config: feePool,
+          positionMint: feePositionMint.publicKey,
+          positionTokenAccount: getAssociatedTokenAddressSync(feePositionMint.publicKey, user.publicKey),
+          position: feePosition,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .signers([user, feePositionMint])
+        .rpc();
+
+      const poolAccount = await program.account.pool.fetch(feePool);
+      expect([poolAccount.minta.toString(), poolAccount.mintb.toString()]).to.include(feeMint.toString());
+    } catch (error) {
+      console.error("Error initializing the transfer fee pool:", error);
+      throw error;
+    }
+  });
+
+  it("Increase liquidity with a transfer fee mint", async () => {
+    try {
+      const liquidity = new anchor.BN(1000000);
+      const initialUser = await feeBalance(userFeeAccount());
+      const initialVault = await feeBalance(feeVault());
+
+      await program.methods
+        .increaseLiquidity(liquidity, new anchor.BN(10000), new anchor.BN(10000), false, null)
+        .accountsStrict(feeLiquidityAccounts())
+        .signers([user])
+        .rpc();
+
+      // The vault holds the deposit net of the fee the user paid on top
+      const paid = initialUser - (await feeBalance(userFeeAccount()));
+      const deposited = (await feeBalance(feeVault())) - initialVault;
+      expect(deposited).to.be.greaterThan(0);
+      expect(deposited).to.equal(paid - transferFee(paid));
+
+      // The same deposit again needs the same payment, and its maximum has to cover the fee too
+      const [tokenMaxA, tokenMaxB] = bySide(paid - 1, 10000);
+      try {
+        await program.methods
+          .increaseLiquidity(liquidity, new anchor.BN(tokenMaxA), new anchor.BN(tokenMaxB), false, null)
+          .accountsStrict(feeLiquidityAccounts())
+          .signers([user])
+          .rpc();
+
+        expect.fail("Expected increase_liquidity to fail");
+      } catch (error) {
+        expect(error.message).to.include("TokenMaxExceeded");
+      }
+    } catch (error) {
+      console.error("Error adding liquidity with a transfer fee mint:", error);
+      throw error;
+    }
+  });
+
+  it("Decrease liquidity with a transfer fee mint", async () => {
+    try {
+      const liquidity = new anchor.BN(200000);
+      const decrease = (tokenMinA: number, tokenMinB: number) =>
+        program.methods
+          .decreaseLiquidity(liquidity, new anchor.BN(tokenMinA), new anchor.BN(tokenMinB), false, null)
+          .accountsStrict(feeLiquidityAccounts())
+          .signers([user])
+          .rpc();
+
+      let initialUser = await feeBalance(userFeeAccount());
+      const initialVault = await feeBalance(feeVault());
+      await decrease(0, 0);
+
+      // The user receives what left the vault net of the fee
+      const withdrawn = initialVault - (await feeBalance(feeVault()));
+      const received = (await feeBalance(userFeeAccount())) - initialUser;
+      expect(withdrawn).to.be.greaterThan(0);
+      expect(received).to.equal(withdrawn - transferFee(withdrawn));
+
+      // The same withdrawal again pays the same, and its minimum is held to what the user receives
+      try {
+        await decrease(...bySide(received + 1, 0));
+
+        expect.fail("Expected decrease_liquidity to fail");
+      } catch (error) {
+        expect(error.message).to.include("TokenMinNotMet");
+      }
+      initialUser = await feeBalance(userFeeAccount());
+      await decrease(...bySide(received, 0));
+      expect((await feeBalance(userFeeAccount())) - initialUser).to.equal(received);
+    } catch (error) {
+      console.error("Error withdrawing liquidity with a transfer fee mint:", error);
+      throw error;
+    }
+  });
+
+  it("Swap paying a transfer fee mint for an exact output", async () => {
+    try {
+      const aToB = feeIsA();
+      const initialSqrtPrice = (await program.account.pool.fetch(feePool)).sqrtPrice;
+      const initialUser = await feeBalance(userFeeAccount());
+      const initialVault = await feeBalance(feeVault());
+      const initialUserA = await balance(userTokenAccountA);
+
+      const tx = await feeSwap(500, false, aToB);
+
+      // Exactly the requested output arrives, and the vault gets the input net of its fee
+      const paid = initialUser - (await feeBalance(userFeeAccount()));
+      const deposited = (await feeBalance(feeVault())) - initialVault;
+      expect((await balance(userTokenAccountA)) - initialUserA).to.equal(500);
+      expect(deposited).to.equal(paid - transferFee(paid));
+
+      // The event reports the input as it reached the vault
+      const [swapped] = (await cpiEvents(tx)).filter((event) => event.name === "swapped");
+      expect(swapped.data.amountIn.toNumber()).to.equal(deposited);
+      expect(swapped.data.amountOut.toNumber()).to.equal(500);
+
+      // From the same price the swap costs the same, and max_amount_in has to cover the fee
+      await restoreFeePoolPrice(initialSqrtPrice, !aToB);
+      try {
+        await feeSwap(500, false, aToB, { maxAmountIn: paid - 1 });
+
+        expect.fail("Expected swap to fail");
+      } catch (error) {
+        expect(error.message).to.include("SlippageExceeded");
+      }
+    } catch (error) {
+      console.error("Error swapping into the transfer fee pool:", error);
+      throw error;
+    }
+  });
+
+  it("Swap receiving a transfer fee mint", async () => {
+    try {
+      const aToB = !feeIsA();
+      const initialSqrtPrice = (await program.account.pool.fetch(feePool)).sqrtPrice;
+      const initialUser = await feeBalance(userFeeAccount());
+      const initialVault = await feeBalance(feeVault());
+
+      const tx = await feeSwap(1000, true, aToB);
+
+      // The user receives what left the vault net of the fee
+      const withdrawn = initialVault - (await feeBalance(feeVault()));
+      const received = (await feeBalance(userFeeAccount())) - initialUser;
+      expect(withdrawn).to.be.greaterThan(0);
+      expect(received).to.equal(withdrawn - transferFee(withdrawn));
+
+      // The event reports the output as it left the vault
+      const [swapped] = (await cpiEvents(tx)).filter((event) => event.name === "swapped");
+      expect(swapped.data.amountOut.toNumber()).to.equal(withdrawn);
+
+      // From the same price the swap pays the same, and min_amount_out is held to what the user receives
+      await restoreFeePoolPrice(initialSqrtPrice, !aToB);
+      try {
+        await feeSwap(1000, true, aToB, { minAmountOut: received + 1 });
+
+        expect.fail("Expected swap to fail");
+      } catch (error) {
+        expect(error.message).to.include("SlippageExceeded");
+      }
+    } catch (error) {
+      console.error("Error swapping out of the transfer fee pool:", error);
+      throw error;
+    }
+  });
+
+  // Routes the user's wSOL through the native pool into token A, then through the fee pool into the fee mint
+  const swapRouteSolToFee = async (amountIn: anchor.BN, minAmountOut: anchor.BN) => {
+    const hop = (
+      hopPool: PublicKey,
+      [hopVaultA, hopVaultB]: PublicKey[],
+      [hopMintA, hopMintB]: PublicKey[],
+      [hopTokenProgramA, hopTokenProgramB]: PublicKey[]
+    ) => [
+      { pubkey: hopPool, isSigner: false, isWritable: true },
+      { pubkey: observationAddress(hopPool), isSigner: false, isWritable: true },
+      { pubkey: tickArrayBitmapAddress(hopPool), isSigner: false, isWritable: false },
+      { pubkey: hopVaultA, isSigner: false, isWritable: true },
+      { pubkey: hopVaultB, isSigner: false, isWritable: true },
+      { pubkey: hopMintA, isSigner: false, isWritable: false },
+      { pubkey: hopMintB, isSigner: false, isWritable: false },
+      { pubkey: hopTokenProgramA, isSigner: false, isWritable: false },
+      { pubkey: hopTokenProgramB, isSigner: false, isWritable: false },
+    ];
+    const nativeTickArrays = await poolTickArrays(nativePool, nativeIsA());
+    const feeTickArrays = await poolTickArrays(feePool, !feeIsA());
+    return program.methods
+      .swapRoute(amountIn, minAmountOut, Buffer.from([nativeTickArrays.length, feeTickArrays.length]), null)
+      .accountsStrict({
+        useraccount: user.publicKey,
+        mintIn: NATIVE_MINT,
+        mintOut: feeMint,
+        usertokenAccountIn: userWsolAccount,
+        usertokenAccountOut: userFeeAccount(),
+        systemProgram: SystemProgram.programId,
+        tokenProgramIn: TOKEN_PROGRAM_ID,
+        tokenProgramOut: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts([
+        ...hop(nativePool, [nativeVaultA, nativeVaultB], [nativeMintA, nativeMintB], [TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID]),
+        ...nativeTickArrays,
+        ...hop(feePool, [feeVaultA, feeVaultB], [feeMintA, feeMintB], [feeTokenProgramA, feeTokenProgramB]),
+        ...feeTickArrays,
+      ])
+      .signers([user])
+      .rpc();
+  };
+
+  it("Swap a route across two pools", async () => {
+    try {
+      // Token A leaves the native pool and enters the fee pool on its way through
+      const feePoolVaultA = feeIsA() ? feeVaultB : feeVaultA;
+      const initialWsol = await balance(userWsolAccount);
+      const initialUserA = await balance(userTokenAccountA);
+      const initialUserFee = await feeBalance(userFeeAccount());
+      const initialNativeVault = await balance(nativeVault());
+      const initialNativeVaultA = await balance(otherVault());
+      const initialFeePoolVaultA = await balance(feePoolVaultA);
+      const initialFeeVault = await feeBalance(feeVault());
+
+      const tx = await swapRouteSolToFee(new anchor.BN(1000), new anchor.BN(1));
+
+      console.log("Two-pool swap route executed with signature:", tx);
+
+      // The user pays wSOL into the first pool and only the fee mint comes back out of the second
+      expect(initialWsol - (await balance(userWsolAccount))).to.equal(1000);
+      expect((await balance(nativeVault())) - initialNativeVault).to.equal(1000);
+      expect(await balance(userTokenAccountA)).to.equal(initialUserA);
+      const handedOver = initialNativeVaultA - (await balance(otherVault()));
+      expect(handedOver).to.be.greaterThan(0);
+      expect((await balance(feePoolVaultA)) - initialFeePoolVaultA).to.equal(handedOver);
+      const withdrawn = initialFeeVault - (await feeBalance(feeVault()));
+      expect(withdrawn).to.be.greaterThan(0);
+      expect((await feeBalance(userFeeAccount())) - initialUserFee).to.equal(withdrawn - transferFee(withdrawn));
+
+      const swapped = (await cpiEvents(tx)).filter((event) => event.name === "swapped");
+      expect(swapped.map((event) => event.data.pool.toString())).to.deep.equal([nativePool.toString(), feePool.toString()]);
+      expect(swapped[0].data.amountIn.toNumber()).to.equal(1000);
+      expect(swapped[0].data.amountOut.toNumber()).to.equal(handedOver);
+      expect(swapped[1].data.amountIn.toNumber()).to.equal(handedOver);
+      expect(swapped[1].data.amountOut.toNumber()).to.equal(withdrawn);
+    } catch (error) {
+      console.error("Error executing the two-pool swap route:", error);
+      throw error;
+    }
+  });
+
+  it("Should fail to swap a route across two pools below the minimum output", async () => {
+    try {
+      // The minimum is held to what reaches the user after the fee mint's transfer fee
+      const initialUserFee = await feeBalance(userFeeAccount());
+      await swapRouteSolToFee(new anchor.BN(1000), new anchor.BN(1));
+      const received = (await feeBalance(userFeeAccount())) - initialUserFee;
+
+      try {
+        // The first route moved both prices against the user, so the same route now pays out no more
+        await swapRouteSolToFee(new anchor.BN(1000), new anchor.BN(received + 1));
+
+        expect.fail("Expected swap_route to fail");
+      } catch (error) {
+        expect(error.message).to.include("SlippageExceeded");
+      }
+      expect((await feeBalance(userFeeAccount())) - initialUserFee).to.equal(received);
+    } catch (error) {
+      console.error("Error checking the two-pool swap route's minimum output:", error);
+      throw error;
+    }
+  });
 
   // it("Should fail with zero amount swap", async () => {
   //   try {
@@ -406,13 +2393,8 @@ describe("clmm", () => {
   //     const aToB = true;
 
   //     await program.methods
-  //       .swap(
-  //         amountIn,
-  //         null,
-  //         null,
-  //         aToB
-  //       )
-  //       .accountsStrict({
+  //       .swap(amountIn, null, null, null, true, aToB, false, null)
+  //       .accounts({
   //         useraccount: user.publicKey,
   //         minta: mintA,
   //         mintb: mintB,
@@ -424,8 +2406,8 @@ describe("clmm", () => {
   //         tokenProgram: TOKEN_PROGRAM_ID,
   //       })
   //       .remainingAccounts([
-  //         { pubkey: tickLower, isSigner: false, isWritable: true },
-  //         { pubkey: tickUpper, isSigner: false, isWritable: true },
+  //         { pubkey: tickArrayUpper, isSigner: false, isWritable: true },
+  //         { pubkey: tickArrayLower, isSigner: false, isWritable: true },
   //       ])
   //       .signers([user])
   //       .rpc();